@@ -0,0 +1,129 @@
+use crate::interval::{Interval, EMPTY_INTERVAL, UNIVERSE_INTERVAL};
+use crate::ray::{Ray, RayProperties};
+use crate::vec3::Vec3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub(crate) x: Interval,
+    pub(crate) y: Interval,
+    pub(crate) z: Interval,
+}
+
+// Constants
+pub const EMPTY_AABB: Aabb = Aabb {
+    x: EMPTY_INTERVAL,
+    y: EMPTY_INTERVAL,
+    z: EMPTY_INTERVAL,
+};
+
+pub const UNIVERSE_AABB: Aabb = Aabb {
+    x: UNIVERSE_INTERVAL,
+    y: UNIVERSE_INTERVAL,
+    z: UNIVERSE_INTERVAL,
+};
+
+impl Default for Aabb {
+    fn default() -> Self {
+        EMPTY_AABB
+    }
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Aabb { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        // Treat the two points as extrema, in any order.
+        Aabb::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Aabb {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        Vec3 {
+            x: 0.5 * (self.x.min + self.x.max),
+            y: 0.5 * (self.y.min + self.y.max),
+            z: 0.5 * (self.z.min + self.z.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0.0 || self.y.size() < 0.0 || self.z.size() < 0.0
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        [self.x, self.y, self.z]
+            .iter()
+            .all(|axis| axis.min.is_finite() && axis.max.is_finite())
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+
+    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+        //Slab test, shrinking ray_t on each axis until it is empty
+        let (ro, rd) = (r.origin(), r.direction());
+        let origin = [ro.x, ro.y, ro.z];
+        let direction = [rd.x, rd.y, rd.z];
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / direction[axis];
+
+            let t0 = (ax.min - origin[axis]) * adinv;
+            let t1 = (ax.max - origin[axis]) * adinv;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if t0 > ray_t.min {
+                ray_t.min = t0;
+            }
+            if t1 < ray_t.max {
+                ray_t.max = t1;
+            }
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn pad_to_minimums(&mut self) {
+        // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::colour::{luminance, Colour};
+use crate::distribution::Distribution2D;
+use crate::math::{Mat3, Mat4};
+use crate::rtweekend::rand0_1;
+use crate::texture::{ImageTexture, Texture};
+use crate::vec3::{Vec3, VectorProperties};
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+//What a ray sees when it leaves the scene without hitting anything. This is also the
+//only light in a scene without emissive materials, so closed indoor scenes use Solid
+//black and are lit by their lamps alone.
+#[derive(Clone, Debug)]
+pub enum Background {
+    Solid(Colour),
+    Gradient { bottom: Colour, top: Colour }, //Blended by the direction's height
+    Environment(Texture),                     //Equirectangular, +Y up
+    EnvironmentLight(Arc<EnvironmentLight>),  //Also sampled directly by diffuse surfaces
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        //The white to sky blue gradient the renderer has always used
+        Background::Gradient {
+            bottom: Colour {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            top: Colour {
+                x: 0.5,
+                y: 0.7,
+                z: 1.0,
+            },
+        }
+    }
+}
+
+impl Background {
+    pub fn value(&self, direction: &Vec3) -> Colour {
+        let unit = direction.unit();
+        match self {
+            Background::Solid(colour) => *colour,
+            Background::Gradient { bottom, top } => {
+                let alpha = 0.5 * (unit.y + 1.0);
+                (1.0 - alpha) * *bottom + alpha * *top
+            }
+            Background::Environment(texture) => {
+                let (u, v) = direction_uv(&unit);
+                texture.value(u, v, &unit)
+            }
+            Background::EnvironmentLight(light) => light.value(&unit),
+            Background::Sky(sky) => sky.value(&unit),
+        }
+    }
+}
+
+//Same mapping as sphere uvs, so a texture looks alike on both: u runs around +Y
+//starting from -X, v from straight down (0) to straight up (1)
+fn direction_uv(unit: &Vec3) -> (f64, f64) {
+    let theta = (-unit.y).clamp(-1.0, 1.0).acos();
+    let phi = (-unit.z).atan2(unit.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+//An equirectangular HDR panorama lighting the scene. Rays that escape look it up like
+//any environment, but it can also be sampled directly: directions are drawn in proportion
+//to texel luminance, with each row weighted by sin(theta) as rows near the poles cover
+//less of the sphere. A small bright sun is then found by shadow rays rather than by lucky
+//bounces.
+pub struct EnvironmentLight {
+    image: ImageTexture,
+    pub(crate) intensity: f64,
+    rotation: Mat3, //About +Y, from the panorama into the world
+    distribution: Distribution2D,
+}
+
+impl fmt::Debug for EnvironmentLight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EnvironmentLight({:?}, intensity {})", self.image, self.intensity)
+    }
+}
+
+impl EnvironmentLight {
+    /// `rotation` turns the panorama about +Y by that many degrees, `intensity` scales it.
+    pub fn new(image: ImageTexture, rotation: f64, intensity: f64) -> Self {
+        let (width, height) = image.dimensions();
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                func.push(luminance(&image.texel(x as i64, y as i64)).max(0.0) * sin_theta);
+            }
+        }
+        let up = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        EnvironmentLight {
+            distribution: Distribution2D::new(&func, width, height),
+            image,
+            intensity,
+            rotation: Mat4::rotation(up, rotation).upper_left(),
+        }
+    }
+
+    fn value(&self, unit: &Vec3) -> Colour {
+        let (u, v) = direction_uv(&(self.rotation.transpose() * *unit));
+        self.intensity * self.image.sample(u, v)
+    }
+
+    /// A unit direction towards the environment, the radiance arriving from it and the
+    /// direction's density over solid angle (zero for the rare unusable sample).
+    pub fn sample(&self) -> (Vec3, Colour, f64) {
+        // s runs across the panorama like u, t down it from straight up to straight down
+        let ((s, t), pdf) = self.distribution.sample(rand0_1(), rand0_1());
+        let (theta, phi) = (PI * t, 2.0 * PI * s);
+        let sin_theta = theta.sin();
+        let local = Vec3 {
+            x: -phi.cos() * sin_theta,
+            y: theta.cos(),
+            z: phi.sin() * sin_theta,
+        };
+        // The map's area element is 2π²sin(theta) times that of the unit square
+        let pdf = match sin_theta > 0.0 {
+            true => pdf / (2.0 * PI * PI * sin_theta),
+            false => 0.0,
+        };
+        (self.rotation * local, self.intensity * self.image.sample(s, 1.0 - t), pdf)
+    }
+
+    /// Density over solid angle with which sample picks the unit `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let local = self.rotation.transpose() * *direction;
+        let sin_theta = (1.0 - local.y * local.y).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // v counts up from straight down where t counts down from straight up
+        let (s, v) = direction_uv(&local);
+        self.distribution.pdf(s, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+//Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999). The
+//sky's luminance and chromaticity in each direction follow the Perez formula, fitted to
+//the sun's height and the turbidity (2 is a very clear sky, 10 a hazy one). Only the sky
+//is modelled, not the sun's disk. Below the horizon the sky is continued as it is at
+//the horizon, which is normally hidden under a ground plane.
+#[derive(Clone, Debug)]
+pub struct Sky {
+    pub(crate) sun: Vec3, //Unit vector towards the sun
+    pub(crate) turbidity: f64,
+    intensity: f64,
+    zenith: [f64; 3],     //Y (kcd/m²), x and y straight up
+    perez: [[f64; 5]; 3], //A to E for Y, x and y
+    perez_sun: [f64; 3],  //F(0, theta_sun) for each, the normalisation
+}
+
+impl Sky {
+    /// `sun` points towards the sun and must be above the horizon. `intensity` scales
+    /// the model's kcd/m² into scene units.
+    pub fn new(sun: Vec3, turbidity: f64, intensity: f64) -> Self {
+        let sun = sun.unit();
+        let t = turbidity;
+        let theta_s = sun.y.clamp(0.0, 1.0).acos();
+        let (t2, th2, th3) = (t * t, theta_s * theta_s, theta_s * theta_s * theta_s);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * theta_s)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * theta_s + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * theta_s + 0.25886);
+        let zenith_y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * theta_s)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * theta_s + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * theta_s + 0.26688);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let perez_sun = perez.map(|coeffs| perez_f(&coeffs, 1.0, theta_s));
+        Sky {
+            sun,
+            turbidity,
+            intensity,
+            zenith: [zenith_luminance.max(0.0), zenith_x, zenith_y],
+            perez,
+            perez_sun,
+        }
+    }
+
+    fn value(&self, unit: &Vec3) -> Colour {
+        // Keep away from the horizon, where 1/cos(theta) in the formula blows up
+        let cos_theta = unit.y.max(0.01);
+        let gamma = (*unit * self.sun).clamp(-1.0, 1.0).acos();
+        let [big_y, x, y]: [f64; 3] =
+            std::array::from_fn(|i| self.zenith[i] * perez_f(&self.perez[i], cos_theta, gamma) / self.perez_sun[i]);
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * big_y;
+        let big_z = (1.0 - x - y) / y * big_y;
+        self.intensity
+            * Colour {
+                x: (3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z).max(0.0),
+                y: (-0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z).max(0.0),
+                z: (0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z).max(0.0),
+            }
+    }
+}
+
+//The Perez sky distribution, theta from the zenith and gamma from the sun
+fn perez_f(coeffs: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coeffs;
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
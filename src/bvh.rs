@@ -0,0 +1,321 @@
+use crate::aabb::{Aabb, EMPTY_AABB};
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::interval::Interval;
+use crate::light::Light;
+use crate::ray::Ray;
+use crate::validate::Report;
+use std::sync::Arc;
+
+const SAH_BUCKETS: usize = 16;
+
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Option<Arc<dyn Hittable>>, //None in leaves holding a single object
+    bbox: Aabb,
+    lights: Vec<Light>, //Those of the whole list, kept by the root node only
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        // Infinite objects such as planes have no useful centroid or area, so they
+        // sit in a plain list beside the tree rather than inside it.
+        let lights = list.lights;
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = list
+            .objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_bounded());
+        if unbounded.is_empty() {
+            return BvhNode {
+                lights,
+                ..BvhNode::build(&mut bounded)
+            };
+        }
+
+        let mut rest = HittableList::new();
+        for object in unbounded {
+            rest.push(object);
+        }
+        let rest: Arc<dyn Hittable> = Arc::new(rest);
+        let (left, right) = match bounded.is_empty() {
+            true => (rest, None),
+            false => (Arc::new(BvhNode::build(&mut bounded)) as Arc<dyn Hittable>, Some(rest)),
+        };
+        let bbox = match &right {
+            Some(rest) => Aabb::surrounding(&left.bounding_box(), &rest.bounding_box()),
+            None => left.bounding_box(),
+        };
+        BvhNode {
+            left,
+            right,
+            bbox,
+            lights,
+        }
+    }
+
+    fn build(objects: &mut [Arc<dyn Hittable>]) -> Self {
+        let bbox = objects
+            .iter()
+            .fold(EMPTY_AABB, |acc, obj| Aabb::surrounding(&acc, &obj.bounding_box()));
+
+        match objects.len() {
+            0 => {
+                return BvhNode {
+                    left: Arc::new(HittableList::new()),
+                    right: None,
+                    bbox,
+                    lights: Vec::new(),
+                };
+            }
+            1 => {
+                return BvhNode {
+                    left: objects[0].clone(),
+                    right: None,
+                    bbox,
+                    lights: Vec::new(),
+                };
+            }
+            2 => {
+                return BvhNode {
+                    left: objects[0].clone(),
+                    right: Some(objects[1].clone()),
+                    bbox,
+                    lights: Vec::new(),
+                };
+            }
+            _ => {}
+        }
+
+        let mid = Self::sah_split(objects);
+        let (lower, upper) = objects.split_at_mut(mid);
+        BvhNode {
+            left: Arc::new(BvhNode::build(lower)),
+            right: Some(Arc::new(BvhNode::build(upper))),
+            bbox,
+            lights: Vec::new(),
+        }
+    }
+
+    /// Reorders `objects` so that the split with the lowest surface area heuristic
+    /// cost lies at the returned index. Objects are binned by bounding box centroid
+    /// along each axis and every bucket boundary is evaluated as a candidate.
+    fn sah_split(objects: &mut [Arc<dyn Hittable>]) -> usize {
+        let centroid_bounds = objects.iter().fold(EMPTY_AABB, |acc, obj| {
+            let c = obj.bounding_box().centroid();
+            Aabb::surrounding(&acc, &Aabb::from_points(c, c))
+        });
+
+        let mut best: Option<(f64, usize, usize)> = None; // (cost, axis, bucket boundary)
+        for axis in 0..3 {
+            let extent = *centroid_bounds.axis_interval(axis);
+            if extent.size() <= 0.0 {
+                continue;
+            }
+            let mut counts = [0usize; SAH_BUCKETS];
+            let mut bounds = [EMPTY_AABB; SAH_BUCKETS];
+            for obj in objects.iter() {
+                let bbox = obj.bounding_box();
+                let b = Self::bucket(&bbox, axis, &extent);
+                counts[b] += 1;
+                bounds[b] = Aabb::surrounding(&bounds[b], &bbox);
+            }
+
+            // Sweep from the right so each boundary's right side is known up front.
+            let mut right_area = [0.0; SAH_BUCKETS];
+            let mut right_count = [0usize; SAH_BUCKETS];
+            let mut acc_box = EMPTY_AABB;
+            let mut acc_count = 0;
+            for b in (1..SAH_BUCKETS).rev() {
+                acc_box = Aabb::surrounding(&acc_box, &bounds[b]);
+                acc_count += counts[b];
+                right_area[b] = acc_box.surface_area();
+                right_count[b] = acc_count;
+            }
+
+            let mut acc_box = EMPTY_AABB;
+            let mut acc_count = 0;
+            for b in 1..SAH_BUCKETS {
+                acc_box = Aabb::surrounding(&acc_box, &bounds[b - 1]);
+                acc_count += counts[b - 1];
+                if acc_count == 0 || right_count[b] == 0 {
+                    continue;
+                }
+                let cost = acc_count as f64 * acc_box.surface_area()
+                    + right_count[b] as f64 * right_area[b];
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        match best {
+            Some((_, axis, boundary)) => {
+                let extent = *centroid_bounds.axis_interval(axis);
+                let mut mid = 0;
+                for i in 0..objects.len() {
+                    if Self::bucket(&objects[i].bounding_box(), axis, &extent) < boundary {
+                        objects.swap(i, mid);
+                        mid += 1;
+                    }
+                }
+                mid
+            }
+            None => objects.len() / 2, // All centroids coincide, any split is as good as another
+        }
+    }
+
+    fn bucket(bbox: &Aabb, axis: usize, extent: &Interval) -> usize {
+        let c = bbox.centroid();
+        let c = [c.x, c.y, c.z][axis];
+        let b = ((c - extent.min) / extent.size() * SAH_BUCKETS as f64) as usize;
+        b.min(SAH_BUCKETS - 1)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        if !self.bbox.hit(r, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let Some(right) = &self.right else {
+            return hit_left;
+        };
+        let right_t = Interval {
+            min: ray_t.min,
+            max: match hit_left {
+                true => rec.as_ref().map_or(ray_t.max, |hit_record| hit_record.t),
+                false => ray_t.max,
+            },
+        };
+        let hit_right = right.hit(r, right_t, rec);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn validate(&self, report: &mut Report) {
+        self.left.validate(report);
+        if let Some(right) = &self.right {
+            right.validate(report);
+        }
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        self.lights.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Sphere;
+    use crate::material::Material;
+    use crate::planar::Plane;
+    use crate::vec3::Vec3;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn point(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    //A scattered field of spheres over a floor plane, the same each run
+    fn scene() -> HittableList {
+        let mut list = HittableList::new();
+        for k in 0..60 {
+            let k = k as f64;
+            list.push(Arc::new(Sphere {
+                centre: point((k * 2.3).sin() * 6.0, (k * 1.7).cos() * 3.0, -8.0 - (k * 0.9).sin() * 4.0),
+                radius: 0.3 + 0.4 * (k * 3.1).sin().abs(),
+                mat_type: Material::default(),
+            }));
+        }
+        list.push(Arc::new(Plane::new(point(0.0, -4.0, 0.0), point(0.0, 1.0, 0.0), Material::default())));
+        list
+    }
+
+    #[test]
+    fn hits_match_a_linear_list() {
+        let list = scene();
+        let mut linear = HittableList::new();
+        for object in &list.objects {
+            linear.push(object.clone());
+        }
+        let bvh = BvhNode::new(list);
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let mut hits = 0;
+        for i in 0..40 {
+            for j in 0..40 {
+                let r = Ray {
+                    origin: point(0.0, 0.0, 2.0),
+                    direction: point(i as f64 / 39.0 - 0.5, j as f64 / 39.0 - 0.5, -1.0),
+                    time: 0.0,
+                };
+                let (mut expected, mut actual) = (None, None);
+                assert_eq!(linear.hit(&r, ray_t, &mut expected), bvh.hit(&r, ray_t, &mut actual));
+                if let (Some(expected), Some(actual)) = (expected, actual) {
+                    assert_eq!(expected.t, actual.t, "ray {} {}", i, j);
+                    hits += 1;
+                }
+            }
+        }
+        // Both spheres and the floor are in view
+        assert!(hits > 800);
+    }
+
+    #[test]
+    fn unbounded_objects_sit_beside_the_tree() {
+        let bvh = BvhNode::new(scene());
+        assert!(bvh.left.bounding_box().is_bounded());
+        assert!(!bvh.right.as_ref().unwrap().bounding_box().is_bounded());
+
+        let mut planes = HittableList::new();
+        planes.push(Arc::new(Plane::new(point(0.0, 0.0, 0.0), point(0.0, 1.0, 0.0), Material::default())));
+        let bvh = BvhNode::new(planes);
+        assert!(bvh.right.is_none());
+    }
+
+    struct Counted {
+        object: Sphere,
+        hits: AtomicUsize,
+    }
+
+    impl Hittable for Counted {
+        fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.object.hit(r, ray_t, rec)
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            self.object.bounding_box()
+        }
+    }
+
+    #[test]
+    fn single_objects_are_tested_once() {
+        let counted = Arc::new(Counted {
+            object: Sphere {
+                centre: point(0.0, 0.0, -2.0),
+                radius: 0.5,
+                mat_type: Material::default(),
+            },
+            hits: AtomicUsize::new(0),
+        });
+        let mut list = HittableList::new();
+        list.push(counted.clone());
+        let bvh = BvhNode::new(list);
+
+        let r = Ray {
+            origin: point(0.0, 0.0, 0.0),
+            direction: point(0.0, 0.0, -1.0),
+            time: 0.0,
+        };
+        let mut rec = None;
+        assert!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_eq!(counted.hits.load(Ordering::Relaxed), 1);
+    }
+}
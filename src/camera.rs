@@ -1,17 +1,21 @@
+use crate::background::Background;
 use crate::colour;
-use crate::colour::{write_colour, Colour};
-use crate::hittable::{HitRecord, Hittable};
-use crate::interval::{Interval, UNIVERSE_INTERVAL};
+use crate::colour::Colour;
+use crate::hittable::Hittable;
+use crate::integrator::{IntegratorKind, MisHeuristic};
+use crate::light::Light;
+use crate::framebuffer::Framebuffer;
 use crate::ray::Ray;
-use crate::rtweekend::rand0_1;
+use crate::rtweekend::{rand0_1, reseed};
 use crate::vec3::{Vec3, VectorProperties};
 use rayon::prelude::*;
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
  pub(crate)  struct  Camera {
     pub(crate) aspect_ratio: f64,
     pub(crate) image_width: i32,
     pub(crate) image_height: i32,
-    pub(crate) max_depth: i32,
+    pub(crate) max_depth: i32, //Hard cap on bounces, paths normally end by Russian roulette first
+    pub(crate) min_bounces: i32, //Bounces every path makes before Russian roulette may end it
     pub(crate) vfov: f64,
     pub(crate) samples_per_pixel: i32,
     pub(crate)centre: Vec3,
@@ -24,13 +28,25 @@ use rayon::prelude::*;
     pub(crate)u: Vec3, //Camera basis vectors
     pub(crate)v: Vec3,
     pub(crate)w:  Vec3,
+    pub(crate) defocus_angle: f64, //Variation angle of rays through each pixel, 0 disables depth of field
+    pub(crate) focus_dist: Option<f64>, //Distance to the plane of perfect focus, None focuses on lookat
+    pub(crate)defocus_disk_u: Vec3, //Defocus disk horizontal radius
+    pub(crate)defocus_disk_v: Vec3,
+    pub(crate) shutter_open: f64, //Rays are cast at random times between these two
+    pub(crate) shutter_close: f64,
+    pub(crate) seed: Option<u64>, //Fixed RNG seed, None draws from entropy
+    pub(crate) background: Background, //Seen by rays that miss every object
+    pub(crate) mis_heuristic: MisHeuristic, //Weighs light samples against scattered rays
+    pub(crate) integrator: IntegratorKind, //Turns each camera ray into a colour
 }
+
 pub trait CameraProperties {
     fn initialize(&mut self) -> Self;
-    fn render(&self, world: &dyn Hittable) -> ();
-    fn ray_colour(r: &Ray, depth: i32, world: &dyn Hittable) -> colour::Colour;
+    fn render(&self, world: &dyn Hittable, lights: &[Light]) -> Framebuffer;
+    fn ray_colour(&self, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> colour::Colour;
     fn get_ray(&self, i: f64, j: f64) -> Ray;
     fn sample_square(&self) -> Vec3;
+    fn defocus_disk_sample(&self) -> Vec3;
 }
 
 impl Camera {
@@ -40,7 +56,8 @@ impl Camera {
             image_width,
             image_height: 0, // Set later in initialize
             samples_per_pixel,
-            max_depth: 10, //default ray depth
+            max_depth: 64, //Far beyond where Russian roulette ends nearly every path
+            min_bounces: 3,
             vfov: 90.0,    //Default vertical view angle.
             lookfrom: Vec3 {
                 x: 0.0,
@@ -65,29 +82,36 @@ impl Camera {
             pixel00_loc: Vec3::default(),
             pixel_delta_u: Vec3::default(),
             pixel_delta_v: Vec3::default(),
-
+            defocus_angle: 0.0,
+            focus_dist: None,
+            defocus_disk_u: Vec3::default(),
+            defocus_disk_v: Vec3::default(),
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            seed: None,
+            background: Background::default(),
+            mis_heuristic: MisHeuristic::default(),
+            integrator: IntegratorKind::default(),
         };
 
         camera.initialize();
         camera
     }
 }
+
 impl CameraProperties for Camera {
     fn initialize(&mut self) -> Camera {
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
         self.image_height = self.image_height.max(1);
         self.centre = self.lookfrom;
         // Determine viewport dimensions.
-        let focal_length = (self.lookfrom - self.lookat).d_euclid();
-        eprintln!("{}",focal_length);
+        let focus_dist = self
+            .focus_dist
+            .unwrap_or_else(|| (self.lookfrom - self.lookat).d_euclid());
         let theta = self.vfov.to_radians();
-        eprintln!("{}",theta);
         let h = (theta / 2.0).tan();
-        eprintln!("{}",h);
-        let viewport_height = 2.0 * h * focal_length ;
-        eprintln!("{}",viewport_height);
+        let viewport_height = 2.0 * h * focus_dist ;
         let viewport_width = viewport_height * self.aspect_ratio;
-        eprintln!("{}",viewport_width);
         //Calculate orthonormal basis for cam coord frame
         self.w = (self.lookfrom - self.lookat).unit();
         self.u = (self.vup.cross(&self.w)).unit();
@@ -102,110 +126,73 @@ impl CameraProperties for Camera {
         self.pixel_delta_v = viewport_v / self.image_height as f64;
 
         // Calculate the location of the upper-left pixel.
-        let viewport_upper_left = self.centre - (focal_length * self.w) -  (viewport_u + viewport_v)/2.0;
+        let viewport_upper_left = self.centre - (focus_dist * self.w) -  (viewport_u + viewport_v)/2.0;
         self.pixel00_loc = viewport_upper_left +  (self.pixel_delta_u + self.pixel_delta_v)/2.0;
+
+        // Calculate the camera defocus disk basis vectors.
+        let defocus_radius = focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
+        self.defocus_disk_u = self.u * defocus_radius;
+        self.defocus_disk_v = self.v * defocus_radius;
         //eprintln!(" w {:?}\n u {:?}\n v{:?}\n lookat {:?}\n lookfrom {:?}",self.w,self.u,self.v,self.lookat,self.lookfrom);
         //eprintln!(" view_u {:?}\n view_v {:?}\n pix_d_u {:?}\n pix_d_v {:?}\n v_u_l {:?}",viewport_u,viewport_v,self.pixel_delta_u,self.pixel_delta_v,viewport_upper_left);
         //eprintln!("pix_loc {:?}", self.pixel00_loc);
-        *self
+        self.clone()
     }
 
     // ...
-    fn render(&self, world: &dyn Hittable) {
+    fn render(&self, world: &dyn Hittable, lights: &[Light]) -> Framebuffer {
         // Create a parallel iterator over the rows and collect the results
-        let pixel_rows: Vec<Vec<Colour>> = (0..self.image_height)
+        let pixel_rows: Vec<Vec<(Colour, u32)>> = (0..self.image_height)
             .into_par_iter()
             .map(|j| {
                 (0..self.image_width)
                     .into_par_iter()
                     .map(|i| {
+                        if let Some(seed) = self.seed {
+                            // Seed per pixel so the result does not depend on thread scheduling
+                            let pixel_index = (j * self.image_width + i) as u64;
+                            reseed(seed ^ pixel_index.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                        }
                         let pixel_centre = self.pixel00_loc
                             + (i as f64 * self.pixel_delta_u)
                             + (j as f64 * self.pixel_delta_v);
                         let _ray_direction = pixel_centre - self.centre;
                         let mut pixel_colour: Vec3 = Default::default();
+                        let mut samples = 0;
                         for _ in 0..self.samples_per_pixel {
                             let ray_r = self.get_ray(i.into(), j.into());
-                            pixel_colour += Self::ray_colour(&ray_r, self.max_depth, world);
+                            pixel_colour += self.ray_colour(&ray_r, world, lights);
+                            samples += 1;
                         }
 
-                        pixel_colour
+                        (pixel_colour, samples)
                     })
                     .collect()
             })
             .collect();
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-        // Flatten the collected results and print them sequentially
-        for row in pixel_rows {
-            for pixel_colour in row {
-                write_colour(&mut std::io::stdout(), pixel_colour, self.samples_per_pixel);
-            }
-        }
-
-        eprintln!("\rDone.");
+        // Flatten the collected rows into a framebuffer of per-pixel means
+        let (sums, samples): (Vec<Colour>, Vec<u32>) = pixel_rows.into_iter().flatten().unzip();
+        Framebuffer::from_sums(self.image_width as usize, self.image_height as usize, sums, samples)
     }
 
-    fn ray_colour(r: &Ray, depth: i32, world: &dyn Hittable) -> colour::Colour {
-        let mut rec: Option<HitRecord> = Some(HitRecord::default());
-        let ret_colour: Vec3;
-        if depth <= 0 {
-            return Colour::default();
-        }
-        if world.hit(
-            r,
-            Interval {
-                min: 0.001,
-                max: UNIVERSE_INTERVAL.max,
-            },
-            &mut rec,
-        ) {
-            let mut scattered = Ray::default();
-            let mut attenuation = Colour::default();
-            let inner_record = rec.as_ref().unwrap();
-            ret_colour = match inner_record.mat_type.scatter(
-                r,
-                &inner_record,
-                &mut attenuation,
-                &mut scattered,
-            ) {
-                //true => attenuation * Self::ray_colour(&scattered, depth - 1, world),
-                true => attenuation.element_wise_multiply(&Self::ray_colour(
-                    &scattered,
-                    depth - 1,
-                    world,
-                )),
-                false => Colour::default(),
-            };
-            //eprintln!("Hit: {:?}", inner_record);
-        } else {
-            let unit_direct = r.direction.unit();
-            let alpha = 0.5 * (unit_direct.y() + 1.0);
-            ret_colour = (1.0 - alpha)
-                * colour::Colour {
-                    x: 1.0,
-                    y: 1.0,
-                    z: 1.0,
-                }
-                + alpha
-                    * colour::Colour {
-                        x: 0.5,
-                        y: 0.7,
-                        z: 1.0,
-                    };
-        }
-
-        ret_colour
+    fn ray_colour(&self, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> colour::Colour {
+        self.integrator.integrator().radiance(self, r, world, lights)
     }
 
     fn get_ray(&self, i: f64, j: f64) -> Ray {
         //Gets a randomly sampled camera ray for pixel at current location
         let pixel_centre = self.pixel00_loc + (i * self.pixel_delta_u) + (j * self.pixel_delta_v);
         let pixel_sample = pixel_centre + self.sample_square();
-        let ray_origin = self.centre;
+        let ray_origin = match self.defocus_angle <= 0.0 {
+            true => self.centre,
+            false => self.defocus_disk_sample(),
+        };
         let ray_direction = pixel_sample - ray_origin;
+        let ray_time = self.shutter_open + rand0_1() * (self.shutter_close - self.shutter_open);
         Ray {
             origin: ray_origin,
             direction: ray_direction,
+            time: ray_time,
         }
     }
 
@@ -214,4 +201,10 @@ impl CameraProperties for Camera {
         let py = -0.5 + rand0_1();
         px * self.pixel_delta_u + py * self.pixel_delta_v
     }
+
+    fn defocus_disk_sample(&self) -> Vec3 {
+        // Returns a random point on the camera defocus disk.
+        let p = Vec3::random_in_unit_disk();
+        self.centre + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
+    }
 }
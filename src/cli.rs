@@ -0,0 +1,111 @@
+use crate::vec3::Vec3;
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+
+/// Renders a scene file, or the built-in demo scene, with a path tracer.
+/// Flags override the settings in the scene's [camera] table.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML scene description or .gltf/.glb file, the built-in demo scene is used when omitted
+    #[arg(long)]
+    pub scene: Option<PathBuf>,
+
+    /// Output image path, "-" writes ASCII PPM to stdout
+    #[arg(short, long, default_value = "image.png")]
+    pub output: PathBuf,
+
+    /// Output format (png, png16, ppm, ppm-ascii, hdr, pfm, exr, exr-uncompressed),
+    /// guessed from the output extension when omitted
+    #[arg(short, long)]
+    pub format: Option<String>,
+
+    /// Image width in pixels [default: 800]
+    #[arg(short = 'w', long)]
+    pub width: Option<i32>,
+
+    /// Width over height [default: 1]
+    #[arg(short, long)]
+    pub aspect_ratio: Option<f64>,
+
+    /// Samples per pixel [default: 10]
+    #[arg(short, long)]
+    pub samples: Option<i32>,
+
+    /// Hard cap on bounces per path, which Russian roulette normally ends well before [default: 64]
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<i32>,
+
+    /// Bounces before Russian roulette may end a path [default: 3]
+    #[arg(long)]
+    pub min_bounces: Option<i32>,
+
+    /// How camera rays are turned into colours [default: path]
+    #[arg(long, value_enum)]
+    pub integrator: Option<IntegratorArg>,
+
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    pub vfov: Option<f64>,
+
+    /// Camera position as x,y,z
+    #[arg(long, allow_hyphen_values = true)]
+    pub lookfrom: Option<Vec3>,
+
+    /// Point the camera looks at as x,y,z
+    #[arg(long, allow_hyphen_values = true)]
+    pub lookat: Option<Vec3>,
+
+    /// Camera up direction as x,y,z
+    #[arg(long, allow_hyphen_values = true)]
+    pub vup: Option<Vec3>,
+
+    /// Depth of field cone angle in degrees, 0 keeps everything in focus
+    #[arg(long)]
+    pub defocus_angle: Option<f64>,
+
+    /// Distance to the plane in perfect focus [default: distance to lookat]
+    #[arg(long)]
+    pub focus_dist: Option<f64>,
+
+    /// Time the shutter opens, for motion blur [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    pub shutter_open: Option<f64>,
+
+    /// Time the shutter closes [default: 1]
+    #[arg(long, allow_hyphen_values = true)]
+    pub shutter_close: Option<f64>,
+
+    /// Worker threads, defaults to one per core
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// RNG seed for reproducible renders
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Clamp linear radiance per channel to this value, removes fireflies
+    #[arg(long)]
+    pub clamp: Option<f64>,
+
+    /// Exposure adjustment in stops, applied before tone mapping
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure: Option<f64>,
+
+    /// Tone mapping curve applied before encoding
+    #[arg(long, value_enum, default_value_t = ToneMapArg::None)]
+    pub tonemap: ToneMapArg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegratorArg {
+    Path,
+    Normals,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMapArg {
+    None,
+    Reinhard,
+    Aces,
+}
@@ -1,26 +1,65 @@
-#[path = "vec3.rs"]
-mod vec3;
-pub use crate::{colour::vec3::Vec3, vec3::Vec3 as Colour};
+pub use crate::vec3::Vec3 as Colour;
 use crate::{interval::Interval, vec3::VectorProperties};
-use std::io::Write;
+use std::io::{self, Write};
 #[inline]
 fn linear_to_gamma(lin: f64) -> f64 {
     lin.sqrt()
 }
-pub fn write_colour(mut handler: impl Write, pixel_colour: Colour, samples_per_pixel: i32) -> () {
-    let scale = 1.0 / samples_per_pixel as f64;
+pub fn to_display(pixel_colour: Colour) -> (f64, f64, f64) {
+    //Gamma corrects a linear colour into [0, 1], which to_u8 and to_u16 quantize
     let intensity: Interval = Interval {
         min: 0.000,
-        max: 0.999,
+        max: 1.000,
     };
-    let rgb: (f64, f64, f64) = (
-        intensity.clamp(linear_to_gamma(pixel_colour.x() * scale)),
-        intensity.clamp(linear_to_gamma(pixel_colour.y() * scale)),
-        intensity.clamp(linear_to_gamma(pixel_colour.z() * scale)),
-    );
+    (
+        intensity.clamp(linear_to_gamma(pixel_colour.x())),
+        intensity.clamp(linear_to_gamma(pixel_colour.y())),
+        intensity.clamp(linear_to_gamma(pixel_colour.z())),
+    )
+}
+#[inline]
+pub fn gamma_to_linear(display: f64) -> f64 {
+    //Inverse of the display transform, for 8-bit colours read from files
+    display * display
+}
+#[inline]
+pub fn luminance(colour: &Colour) -> f64 {
+    //Rec. 709 weights for linear RGB
+    0.2126 * colour.x() + 0.7152 * colour.y() + 0.0722 * colour.z()
+}
+#[inline]
+pub fn to_linear(pixel_colour: Colour) -> [f32; 3] {
+    //No clamping or gamma, for float formats
+    [pixel_colour.x() as f32, pixel_colour.y() as f32, pixel_colour.z() as f32]
+}
+#[inline]
+pub fn to_u8(channel: f64) -> u8 {
+    //256 equal buckets, with 1 itself falling in the top one
+    (256.0 * channel).min(255.0) as u8
+}
+#[inline]
+pub fn to_u16(channel: f64) -> u16 {
+    //Same buckets as to_u8 but 65536 of them, so white is 65535
+    (65536.0 * channel).min(65535.0) as u16
+}
+pub fn write_colour(mut handler: impl Write, pixel_colour: Colour) -> io::Result<()> {
+    let rgb = to_display(pixel_colour);
+    writeln!(handler, "{} {} {}", to_u8(rgb.0), to_u8(rgb.1), to_u8(rgb.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let ir = (256.0 * rgb.0 as f64) as i32;
-    let ig = (256.0 * rgb.1 as f64) as i32;
-    let ib = (256.0 * rgb.2 as f64) as i32;
-    writeln!(handler, "{} {} {}", ir.abs(), ig.abs(), ib.abs()).expect("Failed to write to standard device passed in handler");
+    #[test]
+    fn eight_and_sixteen_bits_quantize_the_same_display_values() {
+        let white = to_display(Colour { x: 1.0, y: 4.0, z: 0.0 });
+        assert_eq!((to_u8(white.0), to_u8(white.1), to_u8(white.2)), (255, 255, 0));
+        assert_eq!((to_u16(white.0), to_u16(white.1), to_u16(white.2)), (65535, 65535, 0));
+        // Each 8-bit bucket holds the 256 16-bit ones that share its high byte
+        for k in 0..=1000 {
+            let display = to_display(Colour { x: k as f64 / 1000.0, y: 0.0, z: 0.0 }).0;
+            assert_eq!((to_u16(display) >> 8) as u8, to_u8(display));
+        }
+    }
 }
@@ -0,0 +1,159 @@
+//Piecewise-constant distributions for importance sampling tabulated functions, such as
+//the brightness of an environment map. Samples land in proportion to the function's
+//value, so bright texels are picked often and dark ones rarely.
+
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>, //func.len() + 1 entries from 0 to 1
+    integral: f64, //Of func over [0, 1]
+}
+
+impl Distribution1D {
+    /// `func` holds non-negative values over equal steps of [0, 1]. An all zero
+    /// function is sampled uniformly.
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = match integral > 0.0 {
+                true => *c / integral,
+                false => i as f64 / n as f64,
+            };
+        }
+        Distribution1D { func, cdf, integral }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` in [0, 1) to a point in [0, 1), its density and the step it is in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // Last entry whose cdf is at most u
+        let i = self.cdf.partition_point(|&c| c <= u).clamp(1, self.func.len()) - 1;
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = match width > 0.0 {
+            true => (u - self.cdf[i]) / width,
+            false => 0.0,
+        };
+        let x = (i as f64 + offset) / self.func.len() as f64;
+        (x, self.pdf(i), i)
+    }
+
+    /// Density of step `i`.
+    pub fn pdf(&self, i: usize) -> f64 {
+        match self.integral > 0.0 {
+            true => self.func[i] / self.integral,
+            false => 1.0,
+        }
+    }
+}
+
+//A marginal distribution picks the row, then that row's own distribution the column
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` is `width` by `height` values, row by row.
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = func
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    /// A point (s, t) in [0, 1)², s across a row and t down the rows, with its density.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (t, pdf_row, row) = self.marginal.sample(u2);
+        let (s, pdf_column, _) = self.rows[row].sample(u1);
+        ((s, t), pdf_row * pdf_column)
+    }
+
+    /// Density with which sample lands on (s, t).
+    pub fn pdf(&self, s: f64, t: f64) -> f64 {
+        let row = ((t * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        let columns = self.rows[row].func.len();
+        let column = ((s * columns as f64) as usize).min(columns - 1);
+        self.marginal.pdf(row) * self.rows[row].pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Evenly spread stand-ins for uniform random numbers
+    fn strata(n: usize) -> impl Iterator<Item = f64> {
+        (0..n).map(move |k| (k as f64 + 0.5) / n as f64)
+    }
+
+    #[test]
+    fn sample_1d_matches_pdf() {
+        let func = vec![1.0, 0.0, 3.0, 4.0];
+        let dist = Distribution1D::new(func.clone());
+        assert_eq!(dist.integral(), 2.0);
+        let mut counts = [0usize; 4];
+        for u in strata(8000) {
+            let (x, pdf, i) = dist.sample(u);
+            assert_eq!(pdf, dist.pdf(i));
+            assert!(x >= i as f64 / 4.0 && x < (i + 1) as f64 / 4.0, "{} outside step {}", x, i);
+            counts[i] += 1;
+        }
+        // Steps are picked in proportion to their value, the empty one never
+        for (count, value) in counts.iter().zip(&func) {
+            assert!((*count as f64 / 8000.0 - value / 8.0).abs() < 1e-3);
+        }
+        // The density integrates to one over [0, 1]
+        let total: f64 = (0..4).map(|i| dist.pdf(i) / 4.0).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zero_function_samples_uniformly() {
+        let dist = Distribution1D::new(vec![0.0; 5]);
+        assert_eq!(dist.integral(), 0.0);
+        for (k, u) in strata(5).enumerate() {
+            let (x, pdf, i) = dist.sample(u);
+            assert_eq!((i, pdf), (k, 1.0));
+            assert!((x - u).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn sample_2d_follows_the_function() {
+        let (width, height) = (4, 3);
+        let func = [
+            0.0, 1.0, 2.0, 0.5, //
+            5.0, 0.0, 0.0, 1.0, //
+            0.25, 0.25, 3.0, 0.0,
+        ];
+        let dist = Distribution2D::new(&func, width, height);
+        let sum: f64 = func.iter().sum();
+        let mut counts = [0usize; 12];
+        let n = 200;
+        for u2 in strata(n) {
+            for u1 in strata(n) {
+                let ((s, t), pdf) = dist.sample(u1, u2);
+                let (column, row) = ((s * width as f64) as usize, (t * height as f64) as usize);
+                let cell = row * width + column;
+                // The density over [0, 1]² of a cell is its share of the total times the cell count
+                assert!((pdf - func[cell] / sum * (width * height) as f64).abs() < 1e-12);
+                assert!((pdf - dist.pdf(s, t)).abs() < 1e-12 * pdf.max(1.0));
+                counts[cell] += 1;
+            }
+        }
+        // Each cell gets its share of the samples, empty ones none
+        for (i, count) in counts.iter().enumerate() {
+            assert!((*count as f64 / (n * n) as f64 - func[i] / sum).abs() < 2e-3, "cell {}", i);
+        }
+    }
+}
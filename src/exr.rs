@@ -0,0 +1,220 @@
+use crate::hdr::check_size;
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::{self, Write};
+
+//Minimal scanline OpenEXR writer: 32-bit float R, G, B channels, no tiles or multipart.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    Zip, //zlib over blocks of 16 scanlines
+}
+
+impl ExrCompression {
+    fn id(&self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn header(width: usize, height: usize, compression: ExrCompression) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]); //Magic number
+    header.extend_from_slice(&[2, 0, 0, 0]); //Version 2, single-part scanline
+
+    // Channels must be listed in alphabetical order
+    let mut chlist = Vec::new();
+    for name in ["B", "G", "R"] {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); //FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]); //pLinear + reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); //x sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); //y sampling
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[compression.id()]);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]); //INCREASING_Y
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0u8; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+    header
+}
+
+fn zip_block(raw: &[u8]) -> io::Result<Vec<u8>> {
+    // Split even and odd bytes into two halves, then delta encode, as ImfZip does
+    let half = raw.len().div_ceil(2);
+    let mut shuffled = vec![0u8; raw.len()];
+    for (i, &byte) in raw.iter().enumerate() {
+        match i % 2 {
+            0 => shuffled[i / 2] = byte,
+            _ => shuffled[half + i / 2] = byte,
+        }
+    }
+    let mut prev = shuffled.first().copied().unwrap_or(0);
+    for byte in shuffled.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(prev).wrapping_add(128);
+        prev = current;
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&shuffled)?;
+    encoder.finish()
+}
+
+pub fn write_exr<W: Write>(
+    mut out: W,
+    width: usize,
+    height: usize,
+    pixels: &[[f32; 3]],
+    compression: ExrCompression,
+) -> io::Result<()> {
+    check_size(width, height)?;
+    let header = header(width, height, compression);
+    let lines = compression.lines_per_block();
+
+    let mut chunks = Vec::new();
+    for (block, rows) in pixels.chunks(width * lines).enumerate() {
+        // Each scanline holds all of B, then all of G, then all of R
+        let mut raw = Vec::with_capacity(rows.len() * 4 * 3);
+        for row in rows.chunks(width) {
+            for channel in [2, 1, 0] {
+                raw.extend(row.iter().flat_map(|p| p[channel].to_le_bytes()));
+            }
+        }
+        let data = match compression {
+            ExrCompression::None => raw,
+            ExrCompression::Zip => {
+                let packed = zip_block(&raw)?;
+                // Blocks that do not shrink are stored as-is, readers detect this by size
+                if packed.len() < raw.len() {
+                    packed
+                } else {
+                    raw
+                }
+            }
+        };
+        let mut chunk = Vec::with_capacity(data.len() + 8);
+        chunk.extend_from_slice(&((block * lines) as i32).to_le_bytes());
+        chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+        chunks.push(chunk);
+    }
+
+    // Offset table entries are absolute file positions of each chunk
+    let mut offset = (header.len() + chunks.len() * 8) as u64;
+    out.write_all(&header)?;
+    for chunk in &chunks {
+        out.write_all(&offset.to_le_bytes())?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in &chunks {
+        out.write_all(chunk)?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    #[test]
+    fn header_layout() {
+        let header = header(5, 3, ExrCompression::Zip);
+        assert_eq!(header[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        assert_eq!(header.last(), Some(&0));
+        for name in [
+            "channels\0chlist\0",
+            "lineOrder\0lineOrder\0",
+            "pixelAspectRatio\0float\0",
+            "screenWindowCenter\0v2f\0",
+            "screenWindowWidth\0float\0",
+        ] {
+            assert!(find(&header, name.as_bytes()).is_some(), "missing {:?}", name);
+        }
+        let compression = find(&header, b"compression\0compression\0").unwrap() + 24;
+        assert_eq!(header[compression..compression + 5], [1, 0, 0, 0, 3]);
+        // Both windows are inclusive boxes from (0, 0) to (4, 2)
+        let window: Vec<u8> = [0i32, 0, 4, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+        for name in ["dataWindow\0box2i\0", "displayWindow\0box2i\0"] {
+            let start = find(&header, name.as_bytes()).unwrap() + name.len() + 4;
+            assert_eq!(header[start..start + 16], window[..]);
+        }
+    }
+
+    #[test]
+    fn uncompressed_scanlines() {
+        let pixels = [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let mut file = Vec::new();
+        write_exr(&mut file, 1, 2, &pixels, ExrCompression::None).unwrap();
+        let header_len = header(1, 2, ExrCompression::None).len();
+
+        // One chunk per scanline, each y, size, then B, G, R
+        let offsets: Vec<usize> = file[header_len..header_len + 16]
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+            .collect();
+        assert_eq!(offsets, [header_len + 16, header_len + 16 + 20]);
+        for (y, (&offset, pixel)) in offsets.iter().zip(pixels).enumerate() {
+            let chunk = &file[offset..offset + 20];
+            assert_eq!(chunk[..8], [y as u8, 0, 0, 0, 12, 0, 0, 0]);
+            let floats: Vec<f32> = chunk[8..]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(floats, [pixel[2], pixel[1], pixel[0]]);
+        }
+        assert_eq!(file.len(), header_len + 16 + 40);
+    }
+
+    #[test]
+    fn zip_blocks_cover_sixteen_lines() {
+        let pixels = vec![[0.5; 3]; 4 * 20];
+        let mut file = Vec::new();
+        write_exr(&mut file, 4, 20, &pixels, ExrCompression::Zip).unwrap();
+        let header_len = header(4, 20, ExrCompression::Zip).len();
+        let second = u64::from_le_bytes(file[header_len + 8..header_len + 16].try_into().unwrap()) as usize;
+        assert_eq!(file[second..second + 4], 16i32.to_le_bytes());
+        // A flat image packs well below its raw 16 lines
+        assert!(second - (header_len + 16) < 16 * 4 * 12);
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        for compression in [ExrCompression::None, ExrCompression::Zip] {
+            let err = write_exr(Vec::new(), 0, 4, &[], compression).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::colour::Colour;
+
+//Linear radiance image produced by Camera::render. Pixels are stored in scanline
+//order, top row first, as the mean of all samples taken for that pixel.
+#[derive(Debug, Clone, Default)]
+pub struct Framebuffer {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<Colour>,
+    pub(crate) samples: Vec<u32>, //Samples behind each pixel's mean
+}
+
+impl Framebuffer {
+    pub fn from_sums(width: usize, height: usize, sums: Vec<Colour>, samples: Vec<u32>) -> Self {
+        //Turns per-pixel sample sums into means, a pixel without samples stays black
+        let pixels = sums
+            .into_iter()
+            .zip(&samples)
+            .map(|(sum, &count)| sum / count.max(1) as f64)
+            .collect();
+        Framebuffer {
+            width,
+            height,
+            pixels,
+            samples,
+        }
+    }
+
+    pub fn pixels(&self) -> &[Colour] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Colour] {
+        &mut self.pixels
+    }
+
+    pub fn samples(&self) -> &[u32] {
+        &self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn means_use_each_pixels_own_sample_count() {
+        let sums = vec![
+            Colour { x: 4.0, y: 2.0, z: 0.0 },
+            Colour { x: 3.0, y: 3.0, z: 3.0 },
+            Colour::default(),
+        ];
+        let image = Framebuffer::from_sums(3, 1, sums, vec![4, 1, 0]);
+        assert_eq!(image.samples(), &[4, 1, 0]);
+        let means: Vec<_> = image.pixels().iter().map(|c| (c.x, c.y, c.z)).collect();
+        assert_eq!(means, [(1.0, 0.5, 0.0), (3.0, 3.0, 3.0), (0.0, 0.0, 0.0)]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    camera::Camera,
+    colour::{gamma_to_linear, Colour},
+    hittable::HittableList,
+    material::Material,
+    math::{Mat4, Normal3, Point3, IDENTITY},
+    mesh::{default_mesh_material, MeshData, TriangleMesh},
+    scene::SceneError,
+    texture::{ImageTexture, Texture, WrapMode},
+    vec3::{Vec3, VectorProperties},
+};
+use gltf::camera::Projection;
+use gltf::image::Format;
+use gltf::texture::WrappingMode;
+use gltf::mesh::Mode;
+use gltf::material::AlphaMode;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+//glTF 2.0 import (.gltf with external or embedded buffers, and .glb). Every mesh
+//instance in the default scene becomes a TriangleMesh with its node transforms baked
+//into the vertices, so instanced meshes are copied. The first perspective camera found
+//sets the view. Of metallic-roughness materials only the base colour (factor and
+//texture) decides the albedo, other textures and emission are ignored.
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3 {
+        x: v[0] as f64,
+        y: v[1] as f64,
+        z: v[2] as f64,
+    }
+}
+
+struct Importer {
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    materials: HashMap<Option<usize>, Material>, //Converted once per glTF material
+    world: HittableList,
+    camera: Option<Camera>,
+}
+
+impl Importer {
+    fn visit(&mut self, node: gltf::Node, parent: &Mat4) {
+        // glTF matrices are column-major, Mat4 is indexed m[row][column]
+        let columns = node.transform().matrix();
+        let local = Mat4 {
+            m: std::array::from_fn(|r| std::array::from_fn(|c| columns[c][r] as f64)),
+        };
+        let transform = *parent * local;
+        if let Some(mesh) = node.mesh() {
+            let data = self.mesh(&mesh, &transform);
+            if !data.indices.is_empty() {
+                self.world.push(Arc::new(TriangleMesh::new(data)));
+            }
+        }
+        if let (Some(camera), None) = (node.camera(), &self.camera) {
+            self.camera = perspective_camera(&camera, &transform);
+        }
+        for child in node.children() {
+            self.visit(child, &transform);
+        }
+    }
+
+    fn mesh(&mut self, mesh: &gltf::Mesh, transform: &Mat4) -> MeshData {
+        // A mirroring transform (det < 0) reverses the winding, which is undone by
+        // swapping two corners of every triangle
+        let det = transform.upper_left().determinant();
+        let normal_matrix = transform.normal_matrix().unwrap_or_default();
+
+        let mut data = MeshData::default();
+        let primitives: Vec<_> = mesh
+            .primitives()
+            .filter(|p| matches!(p.mode(), Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan))
+            .collect();
+        let any_normals = primitives.iter().any(|p| p.get(&gltf::Semantic::Normals).is_some());
+        let any_uvs = primitives.iter().any(|p| p.get(&gltf::Semantic::TexCoords(0)).is_some());
+        let any_colours = primitives.iter().any(|p| p.get(&gltf::Semantic::Colors(0)).is_some());
+
+        for primitive in primitives {
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(iter) => iter.collect(),
+                None => continue,
+            };
+            let base = data.positions.len() as u32;
+            let count = positions.len();
+            data.positions
+                .extend(positions.iter().map(|p| (*transform * Point3(vec3(*p))).0));
+
+            // Attributes are all or nothing per mesh, so fill in for primitives lacking one
+            if any_normals {
+                match reader.read_normals() {
+                    Some(iter) => data
+                        .normals
+                        .extend(iter.map(|n| (normal_matrix * Normal3(vec3(n))).unit().0)),
+                    None => data.normals.extend(std::iter::repeat_n(Vec3::default(), count)),
+                }
+            }
+            if any_uvs {
+                // glTF puts v = 0 at the top of an image, this renderer at the bottom
+                match reader.read_tex_coords(0) {
+                    Some(uvs) => data
+                        .uvs
+                        .extend(uvs.into_f32().map(|[u, v]| (u as f64, 1.0 - v as f64))),
+                    None => data.uvs.extend(std::iter::repeat_n((0.0, 0.0), count)),
+                }
+            }
+            if any_colours {
+                let white = Colour {
+                    x: 1.0,
+                    y: 1.0,
+                    z: 1.0,
+                };
+                match reader.read_colors(0) {
+                    Some(colours) => data.colours.extend(colours.into_rgb_f32().map(|[r, g, b]| Colour {
+                        x: r as f64,
+                        y: g as f64,
+                        z: b as f64,
+                    })),
+                    None => data.colours.extend(std::iter::repeat_n(white, count)),
+                }
+            }
+
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..count as u32).collect(),
+            };
+            let triangles: Vec<[u32; 3]> = match primitive.mode() {
+                Mode::TriangleStrip => (2..indices.len())
+                    .map(|i| match i % 2 {
+                        0 => [indices[i - 2], indices[i - 1], indices[i]],
+                        _ => [indices[i - 1], indices[i - 2], indices[i]],
+                    })
+                    .collect(),
+                Mode::TriangleFan => (2..indices.len())
+                    .map(|i| [indices[0], indices[i - 1], indices[i]])
+                    .collect(),
+                _ => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            };
+
+            let material = data.materials.len() as u32;
+            data.materials.push(self.material(&primitive.material()));
+            for [a, b, c] in triangles {
+                if [a, b, c].iter().any(|&i| i as usize >= count) {
+                    continue;
+                }
+                data.indices.push(match det < 0.0 {
+                    true => [base + a, base + c, base + b],
+                    false => [base + a, base + b, base + c],
+                });
+                data.face_materials.push(material);
+            }
+        }
+        data
+    }
+}
+
+impl Importer {
+    fn material(&mut self, material: &gltf::Material) -> Material {
+        if let Some(converted) = self.materials.get(&material.index()) {
+            return converted.clone();
+        }
+        let converted = self.convert_material(material);
+        self.materials.insert(material.index(), converted.clone());
+        converted
+    }
+
+    fn convert_material(&self, material: &gltf::Material) -> Material {
+        //Primitives without a material get the same grey as other mesh formats rather
+        //than the spec's rough white metal
+        if material.index().is_none() {
+            return default_mesh_material(0.5);
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor();
+        let factor = Colour {
+            x: r as f64,
+            y: g as f64,
+            z: b as f64,
+        };
+        // The base colour texture is scaled by the factor
+        let image = pbr.base_color_texture().and_then(|info| {
+            let texture = info.texture();
+            let wrap = match texture.sampler().wrap_s() {
+                WrappingMode::ClampToEdge => WrapMode::Clamp,
+                WrappingMode::MirroredRepeat => WrapMode::Mirror,
+                WrappingMode::Repeat => WrapMode::Repeat,
+            };
+            image_texture(&self.images[texture.source().index()], wrap)
+        });
+        let albedo = match image {
+            Some(image) if [r, g, b] == [1.0; 3] => Texture::Image(Arc::new(image)),
+            Some(image) => Texture::Image(Arc::new(image.tinted(&factor))),
+            None => Texture::Solid(factor),
+        };
+
+        let transmission = material.transmission().map_or(0.0, |t| t.transmission_factor());
+        let translucent = material.alpha_mode() == AlphaMode::Blend && alpha < 1.0;
+        if transmission > 0.5 || translucent {
+            Material::Dielectric {
+                idx_refract: material.ior().unwrap_or(1.5) as f64,
+            }
+        } else if pbr.metallic_factor() > 0.5 {
+            Material::Metal {
+                albedo,
+                fuzz: pbr.roughness_factor() as f64,
+            }
+        } else {
+            Material::Lambertian { albedo }
+        }
+    }
+}
+
+fn image_texture(data: &gltf::image::Data, wrap: WrapMode) -> Option<ImageTexture> {
+    //8 and 16-bit images hold sRGB-style encoded colour, float ones linear
+    let (channels, bytes) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let sample = |at: usize| -> f64 {
+        let raw = &data.pixels[at * bytes..(at + 1) * bytes];
+        match bytes {
+            1 => gamma_to_linear(raw[0] as f64 / 255.0),
+            2 => gamma_to_linear(u16::from_ne_bytes([raw[0], raw[1]]) as f64 / 65535.0),
+            _ => f32::from_ne_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+        }
+    };
+    let (width, height) = (data.width as usize, data.height as usize);
+    if width == 0 || height == 0 || data.pixels.len() < width * height * channels * bytes {
+        return None;
+    }
+    let pixels = (0..width * height)
+        .map(|i| {
+            let at = i * channels;
+            // Grey images, with or without alpha, repeat their first channel
+            let (r, g, b) = match channels {
+                1 | 2 => (at, at, at),
+                _ => (at, at + 1, at + 2),
+            };
+            Colour {
+                x: sample(r),
+                y: sample(g),
+                z: sample(b),
+            }
+        })
+        .collect();
+    Some(ImageTexture::new(width, height, pixels, wrap))
+}
+
+fn perspective_camera(camera: &gltf::Camera, transform: &Mat4) -> Option<Camera> {
+    //glTF cameras look down their local -Z with +Y up
+    let perspective = match camera.projection() {
+        Projection::Perspective(perspective) => perspective,
+        Projection::Orthographic(_) => return None,
+    };
+    let mut out = Camera::new(perspective.aspect_ratio().map_or(1.0, |a| a as f64), 800, 10);
+    out.vfov = (perspective.yfov() as f64).to_degrees();
+    let forward = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: -1.0,
+    };
+    let up = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+    out.lookfrom = (*transform * Point3::default()).into();
+    out.lookat = out.lookfrom + (*transform * forward).unit();
+    out.vup = (*transform * up).unit();
+    Some(out)
+}
+
+/// Loads the default scene (or the first one) of a .gltf or .glb file. Without a
+/// perspective camera in the file the usual default camera is returned.
+pub fn load_gltf(path: &Path) -> Result<(HittableList, Camera), SceneError> {
+    let error = |err: gltf::Error| SceneError::Import(path.to_path_buf(), err.to_string());
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(error)?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob).map_err(error)?;
+    let images = gltf::import_images(&document, path.parent(), &buffers).map_err(error)?;
+    let mut importer = Importer {
+        buffers,
+        images,
+        materials: HashMap::new(),
+        world: HittableList::new(),
+        camera: None,
+    };
+    if let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) {
+        for node in scene.nodes() {
+            importer.visit(node, &IDENTITY);
+        }
+    }
+    let camera = importer.camera.unwrap_or_else(|| Camera::new(1.0, 800, 10));
+    Ok((importer.world, camera))
+}
@@ -0,0 +1,302 @@
+use std::io::{self, Write};
+
+//Radiance RGBE (.hdr) encoding. Pixels are linear RGB, one [f32; 3] per pixel in scanline order.
+
+pub fn to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    let v = rgb[0].max(rgb[1]).max(rgb[2]);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // frexp: v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f32.powi(e);
+    if m >= 1.0 {
+        m *= 0.5;
+        e += 1;
+    }
+    let scale = m * 256.0 / v;
+    [
+        (rgb[0].max(0.0) * scale) as u8,
+        (rgb[1].max(0.0) * scale) as u8,
+        (rgb[2].max(0.0) * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+//Shared by the float writers, which walk the pixels a row at a time and cannot with no rows or columns
+pub fn check_size(width: usize, height: usize) -> io::Result<()> {
+    match width == 0 || height == 0 {
+        true => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot write an empty {}x{} image", width, height),
+        )),
+        false => Ok(()),
+    }
+}
+
+pub fn write_hdr<W: Write>(mut out: W, width: usize, height: usize, pixels: &[[f32; 3]]) -> io::Result<()> {
+    check_size(width, height)?;
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+    let mut line = Vec::with_capacity(width * 4);
+    for row in pixels.chunks(width) {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(|&p| to_rgbe(p)).collect();
+        line.clear();
+        // New-style RLE only covers these widths, anything else is written flat
+        if !(8..=0x7fff).contains(&width) {
+            line.extend(rgbe.iter().flatten());
+        } else {
+            line.extend_from_slice(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+            for component in 0..4 {
+                let data: Vec<u8> = rgbe.iter().map(|p| p[component]).collect();
+                rle_encode(&data, &mut line);
+            }
+        }
+        out.write_all(&line)?;
+    }
+    out.flush()
+}
+
+fn rle_encode(data: &[u8], out: &mut Vec<u8>) {
+    //Runs of at least MIN_RUN identical bytes are packed, everything else is copied literally
+    const MIN_RUN: usize = 4;
+    let n = data.len();
+    let mut cur = 0;
+    while cur < n {
+        let mut beg_run = cur;
+        let mut run_count = 0;
+        let mut old_run_count = 0;
+        while run_count < MIN_RUN && beg_run < n {
+            beg_run += run_count;
+            old_run_count = run_count;
+            run_count = 1;
+            while beg_run + run_count < n && run_count < 127 && data[beg_run] == data[beg_run + run_count] {
+                run_count += 1;
+            }
+        }
+        // A short run right before the long one is still worth packing
+        if old_run_count > 1 && old_run_count == beg_run - cur {
+            out.push((128 + old_run_count) as u8);
+            out.push(data[cur]);
+            cur = beg_run;
+        }
+        while cur < beg_run {
+            let literal = (beg_run - cur).min(128);
+            out.push(literal as u8);
+            out.extend_from_slice(&data[cur..cur + literal]);
+            cur += literal;
+        }
+        if run_count >= MIN_RUN {
+            out.push((128 + run_count) as u8);
+            out.push(data[beg_run]);
+            cur += run_count;
+        }
+    }
+}
+
+pub fn write_pfm<W: Write>(mut out: W, width: usize, height: usize, pixels: &[[f32; 3]]) -> io::Result<()> {
+    check_size(width, height)?;
+    //Negative scale marks little-endian data, rows are stored bottom to top
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+    let mut line = Vec::with_capacity(width * 12);
+    for row in pixels.chunks(width).rev() {
+        line.clear();
+        line.extend(row.iter().flatten().flat_map(|c| c.to_le_bytes()));
+        out.write_all(&line)?;
+    }
+    out.flush()
+}
+
+pub fn from_rgbe(rgbe: [u8; 4]) -> [f32; 3] {
+    if rgbe[3] == 0 {
+        return [0.0; 3];
+    }
+    // Mantissas are taken at the centre of their bucket, as Radiance does
+    let scale = 2f32.powi(rgbe[3] as i32 - (128 + 8));
+    [
+        (rgbe[0] as f32 + 0.5) * scale,
+        (rgbe[1] as f32 + 0.5) * scale,
+        (rgbe[2] as f32 + 0.5) * scale,
+    ]
+}
+
+/// Decodes a Radiance file with the usual "-Y height +X width" orientation into
+/// width, height and top-down linear RGB pixels. Both flat and new-style RLE
+/// scanlines are accepted.
+pub fn read_hdr(data: &[u8]) -> Result<(usize, usize, Vec<[f32; 3]>), String> {
+    let mut pos = 0;
+    let mut next_line = || {
+        let start = pos;
+        let end = data[start..].iter().position(|&b| b == b'\n').map(|i| start + i)?;
+        pos = end + 1;
+        Some(String::from_utf8_lossy(&data[start..end]).into_owned())
+    };
+    let magic = next_line().unwrap_or_default();
+    if !magic.starts_with("#?") {
+        return Err("not a Radiance HDR file".to_string());
+    }
+    loop {
+        match next_line() {
+            None => return Err("header ends before the resolution line".to_string()),
+            Some(line) if line.is_empty() => break,
+            Some(line) if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" => {
+                return Err(format!("unsupported pixel format '{}'", &line[7..]))
+            }
+            Some(_) => {}
+        }
+    }
+    let resolution = next_line().unwrap_or_default();
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) => (h, w),
+            _ => return Err(format!("bad resolution line '{}'", resolution)),
+        },
+        _ => return Err(format!("unsupported resolution line '{}'", resolution)),
+    };
+
+    let truncated = || "pixel data is truncated".to_string();
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut line = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        let rle = (8..=0x7fff).contains(&width)
+            && data.get(pos..pos + 4) == Some(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+        if rle {
+            pos += 4;
+            for component in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = *data.get(pos).ok_or_else(truncated)? as usize;
+                    pos += 1;
+                    if count > 128 {
+                        let value = *data.get(pos).ok_or_else(truncated)?;
+                        pos += 1;
+                        for pixel in line.iter_mut().skip(x).take(count - 128) {
+                            pixel[component] = value;
+                        }
+                        x += count - 128;
+                    } else {
+                        let bytes = data.get(pos..pos + count).ok_or_else(truncated)?;
+                        pos += count;
+                        for (pixel, value) in line.iter_mut().skip(x).zip(bytes) {
+                            pixel[component] = *value;
+                        }
+                        x += count;
+                    }
+                    if count == 0 || x > width {
+                        return Err("corrupt run-length data".to_string());
+                    }
+                }
+            }
+        } else {
+            let bytes = data.get(pos..pos + width * 4).ok_or_else(truncated)?;
+            pos += width * 4;
+            for (pixel, chunk) in line.iter_mut().zip(bytes.chunks_exact(4)) {
+                pixel.copy_from_slice(chunk);
+            }
+        }
+        pixels.extend(line.iter().map(|&p| from_rgbe(p)));
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Vec<[f32; 3]> {
+        //Flat runs on the left half for RLE to pack, varying values on the right
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                match x < width / 2 {
+                    true => [0.5, 0.25, 2.0],
+                    false => [x as f32 * 0.1, y as f32 + 0.5, 100.0 / (x + 1) as f32],
+                }
+            })
+            .collect()
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        //RGBE keeps 8 bits of mantissa for the brightest channel
+        let tolerance = a[0].max(a[1]).max(a[2]) / 128.0;
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() <= tolerance, "{:?} vs {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn rgbe_round_trip() {
+        for rgb in [[1.0, 1.0, 1.0], [0.5, 0.25, 0.125], [1000.0, 3.0, 0.0], [1e-3, 2e-3, 5e-4]] {
+            assert_close(rgb, from_rgbe(to_rgbe(rgb)));
+        }
+        assert_eq!(to_rgbe([0.0; 3]), [0, 0, 0, 0]);
+        assert_eq!(from_rgbe([0, 0, 0, 0]), [0.0; 3]);
+        // Negative channels are clamped rather than wrapped
+        assert_eq!(to_rgbe([-1.0, 1.0, 1.0])[0], 0);
+    }
+
+    #[test]
+    fn rle_scanlines_round_trip() {
+        let (width, height) = (40, 3);
+        let pixels = gradient(width, height);
+        let mut file = Vec::new();
+        write_hdr(&mut file, width, height, &pixels).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 3 +X 40\n";
+        assert!(file.starts_with(header));
+        assert_eq!(file[header.len()..header.len() + 4], [2, 2, 0, 40]);
+        // The flat runs must have been packed
+        assert!(file.len() < header.len() + width * height * 4);
+
+        let (w, h, decoded) = read_hdr(&file).unwrap();
+        assert_eq!((w, h), (width, height));
+        for (a, b) in pixels.iter().zip(decoded) {
+            assert_close(*a, b);
+        }
+    }
+
+    #[test]
+    fn narrow_images_use_flat_scanlines() {
+        let pixels = gradient(4, 2);
+        let mut file = Vec::new();
+        write_hdr(&mut file, 4, 2, &pixels).unwrap();
+        let header_len = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 4\n".len();
+        assert_eq!(file.len(), header_len + 4 * 2 * 4);
+        let (_, _, decoded) = read_hdr(&file).unwrap();
+        for (a, b) in pixels.iter().zip(decoded) {
+            assert_close(*a, b);
+        }
+    }
+
+    #[test]
+    fn read_hdr_rejects_bad_input() {
+        assert!(read_hdr(b"P6\n1 1\n255\n").is_err());
+        assert!(read_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0").is_err());
+        assert!(read_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0").is_err());
+        assert_eq!(read_hdr(b"#?RADIANCE\n\n-Y 2 +X 1\n\0\0\0\0"), Err("pixel data is truncated".to_string()));
+    }
+
+    #[test]
+    fn pfm_header_and_row_order() {
+        let pixels = [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let mut file = Vec::new();
+        write_pfm(&mut file, 1, 2, &pixels).unwrap();
+        let header = b"PF\n1 2\n-1.0\n";
+        assert!(file.starts_with(header));
+        let floats: Vec<f32> = file[header.len()..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        // Bottom row first
+        assert_eq!(floats, [4.0, 5.0, 6.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        for (width, height) in [(0, 2), (2, 0)] {
+            let err = write_hdr(Vec::new(), width, height, &[]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = write_pfm(Vec::new(), width, height, &[]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{
+    aabb::{Aabb, EMPTY_AABB},
     interval,
     ray::{Ray, RayProperties},
     vec3::{Vec3, VectorProperties},
+    light::{Light, LightShape},
     material::Material,
+    validate::{fmt_vec3, Report},
 };
 use interval::Interval;
-use std::{sync::Arc, vec::Vec};
+use std::{f64::consts::PI, sync::Arc, vec::Vec};
 
 #[derive(Clone,Default,Debug)]
 pub struct HitRecord {
@@ -13,7 +16,10 @@ pub struct HitRecord {
     pub(crate) n: Vec3,//Normal
     pub(crate) mat_type: Material,
     pub(crate) t: f64,//t parametre
+    pub(crate) u: f64,//Surface coordinates for texture lookups
+    pub(crate) v: f64,
     pub(crate) front_face: bool,
+    pub(crate) area_pdf: f64, //Density of this point when its light is sampled by area, 0 if it never is
 }
 
 
@@ -25,17 +31,33 @@ pub struct Sphere {
     pub(crate) radius: f64,
     pub(crate) mat_type: Material,
 }
+#[derive(Default)]
+pub struct MovingSphere {
+    pub(crate) centre0: Vec3, //Centre at time0
+    pub(crate) centre1: Vec3, //Centre at time1
+    pub(crate) time0: f64,
+    pub(crate) time1: f64,
+    pub(crate) radius: f64,
+    pub(crate) mat_type: Material,
+}
+#[derive(Default)]
 pub struct HittableList {
     pub(crate) objects: Vec<Arc<dyn Hittable>>,
+    pub(crate) lights: Vec<Light>, //Gathered from the objects as they are pushed
+    bbox: Aabb,
 }
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
             objects: Vec::new(),
+            lights: Vec::new(),
+            bbox: EMPTY_AABB,
         }
     }
 
     pub fn push(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
+        self.lights.extend(object.lights());
         self.objects.push(object);
     }
 }
@@ -60,6 +82,11 @@ impl Hittable for Vec<Box<dyn Hittable>> {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter()
+            .fold(EMPTY_AABB, |acc, object| Aabb::surrounding(&acc, &object.bounding_box()))
+    }
 }
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
@@ -84,71 +111,202 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn validate(&self, report: &mut Report) {
+        for object in &self.objects {
+            object.validate(report);
+        }
+        for light in &self.lights {
+            report.check_light(light);
+        }
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        self.lights.clone()
+    }
 }
 
 
 
-trait HitRecordProperties {
-    fn set_face_normal(r: &Ray, outward_normal: &Vec3, rec: &mut Option<HitRecord>) -> ();
+pub(crate) trait HitRecordProperties {
+    fn set_face_normal(r: &Ray, outward_normal: &Vec3, rec: &mut Option<HitRecord>);
 }
 impl HitRecordProperties for HitRecord {
-    fn set_face_normal(r: &Ray, outward_normal: &Vec3, rec: &mut Option<HitRecord>) -> () {
+    fn set_face_normal(r: &Ray, outward_normal: &Vec3, rec: &mut Option<HitRecord>) {
         let front_face = r.direction() * outward_normal < 0.0;
-        let mut norm = outward_normal.clone();
+        let mut norm = *outward_normal;
         if front_face {
         } else {
             norm = -norm;
         };
-        rec.as_mut().map(|hit_record| {
-            hit_record.n = norm.clone();
-        });
+        if let Some(hit_record) = rec.as_mut() {
+            hit_record.n = norm;
+            hit_record.front_face = front_face;
+        }
     }
 }
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool;
+    fn bounding_box(&self) -> Aabb;
+    fn validate(&self, _report: &mut Report) {}
+    /// Emitters inside this object that can be sampled directly. Whatever is listed
+    /// here must report its density in HitRecord::area_pdf, and nothing else may.
+    fn lights(&self) -> Vec<Light> {
+        Vec::new()
+    }
+}
+
+pub fn sphere_uv(p: &Vec3) -> (f64, f64) {
+    // p: a given point on the sphere of radius one, centered at the origin.
+    // u: returned value [0,1] of angle around the Y axis from X=-1.
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+fn hit_sphere(
+    centre: Vec3,
+    radius: f64,
+    mat_type: &Material,
+    area_pdf: f64,
+    r: &Ray,
+    ray_t: Interval,
+    rec: &mut Option<HitRecord>,
+) -> bool {
+    let oc = r.origin - centre;
+    let a = r.direction.d_euclidsq();
+    let half_b = oc * r.direction;
+    let c = oc.d_euclidsq() - radius * radius;
+    let discrim = half_b * half_b - a * c;
+
+    if discrim <= 0.0 {
+        return false;
+    }
+
+    let root = discrim.sqrt();
+    let temp = {
+        let t1 = (-half_b - root) / a;
+        let t2 = (-half_b + root) / a;
+        if ray_t.surrounds(t1) {
+            t1
+        } else if ray_t.surrounds(t2) {
+            t2
+        } else {
+            return false;
+        }
+    };
+
+    let hit_point = r.at(temp);
+    let outward_norm = (hit_point - centre) / radius;
+    let front_face = r.direction * outward_norm < 0.0;
+    let (u, v) = sphere_uv(&((hit_point - centre) / radius.abs()));
+
+    *rec = Some(HitRecord {
+        p: hit_point,
+        n: if front_face {
+            outward_norm
+        } else {
+            -outward_norm
+        },
+        mat_type: mat_type.clone(),
+        t: temp,
+        u,
+        v,
+        front_face,
+        area_pdf,
+    });
+
+    true
+}
+
+fn sphere_box(centre: Vec3, radius: f64) -> Aabb {
+    //abs() so hollow spheres with a negative radius still get a valid box
+    let rvec = Vec3 {
+        x: radius.abs(),
+        y: radius.abs(),
+        z: radius.abs(),
+    };
+    Aabb::from_points(centre - rvec, centre + rvec)
+}
+
+fn validate_sphere(context: &str, radius: f64, mat_type: &Material, report: &mut Report) {
+    if radius == 0.0 || !radius.is_finite() {
+        report.error(format!("{}: radius must be finite and non-zero", context));
+    } else if radius < 0.0 && !matches!(mat_type, Material::Dielectric { .. }) {
+        report.warn(format!(
+            "{}: negative radius flips the normals inward, only meaningful for hollow dielectric shells",
+            context
+        ));
+    }
+    report.check_material(mat_type, context);
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
-        let oc = r.origin - self.centre;
-        let a = r.direction.d_euclidsq();
-        let half_b = oc * r.direction;
-        let c = oc.d_euclidsq() - self.radius * self.radius;
-        let discrim = half_b * half_b - a * c;
+        let area_pdf = self.mat_type.light_area_pdf(4.0 * PI * self.radius * self.radius);
+        hit_sphere(self.centre, self.radius, &self.mat_type, area_pdf, r, ray_t, rec)
+    }
 
-        if discrim <= 0.0 {
-            return false;
+    fn bounding_box(&self) -> Aabb {
+        sphere_box(self.centre, self.radius)
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!("sphere at {} radius {}", fmt_vec3(&self.centre), self.radius);
+        validate_sphere(&context, self.radius, &self.mat_type, report);
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        match &self.mat_type {
+            Material::DiffuseLight { emit } => vec![Light::new(
+                LightShape::Sphere {
+                    centre: self.centre,
+                    radius: self.radius,
+                },
+                emit.clone(),
+            )],
+            _ => Vec::new(),
         }
+    }
+}
 
-        let root = discrim.sqrt();
-        let temp = {
-            let t1 = (-half_b - root) / a;
-            let t2 = (-half_b + root) / a;
-            if ray_t.surrounds(t1) {
-                t1
-            } else if ray_t.surrounds(t2) {
-                t2
-            } else {
-                return false;
-            }
+impl MovingSphere {
+    pub fn centre(&self, time: f64) -> Vec3 {
+        //Linear motion between the two keyframes, at rest outside them
+        let span = self.time1 - self.time0;
+        let s = match span > 0.0 {
+            true => ((time - self.time0) / span).clamp(0.0, 1.0),
+            false => 0.0,
         };
+        self.centre0 + s * (self.centre1 - self.centre0)
+    }
+}
 
-        let hit_point = r.at(temp);
-        let outward_norm = (hit_point - self.centre) / self.radius;
-        let front_face = r.direction * outward_norm < 0.0;
-
-        *rec = Some(HitRecord {
-            p: hit_point,
-            n: if front_face {
-                outward_norm
-            } else {
-                -outward_norm
-            },
-            mat_type: self.mat_type,
-            t: temp,
-            front_face,
-        });
-
-        true
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        // Not a sampled light, it would have to be sampled where it is at the ray's time
+        hit_sphere(self.centre(r.time()), self.radius, &self.mat_type, 0.0, r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::surrounding(&sphere_box(self.centre0, self.radius), &sphere_box(self.centre1, self.radius))
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!(
+            "moving sphere from {} to {} radius {}",
+            fmt_vec3(&self.centre0),
+            fmt_vec3(&self.centre1),
+            self.radius
+        );
+        if self.time1 < self.time0 {
+            report.error(format!("{}: time1 {} is before time0 {}", context, self.time1, self.time0));
+        }
+        validate_sphere(&context, self.radius, &self.mat_type, report);
     }
 }
@@ -0,0 +1,238 @@
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::colour::Colour;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::{Interval, UNIVERSE_INTERVAL};
+use crate::light::{Light, LightSample};
+use crate::material::ScatterRecord;
+use crate::ray::{Ray, RayProperties};
+use crate::rtweekend::rand0_1;
+use crate::vec3::VectorProperties;
+
+//Integrators turn a camera ray into an estimate of the light arriving along it. The
+//camera picks one by its IntegratorKind and asks it for every sample, so adding another
+//needs an implementation and a variant but no change to Camera::render. Settings such as
+//max_depth and the background stay on the camera, where scenes and flags set them.
+
+pub trait Integrator {
+    /// One estimate of the radiance reaching the camera along `r`.
+    fn radiance(&self, camera: &Camera, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> Colour;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum IntegratorKind {
+    #[default]
+    Path,
+    Normals,
+}
+
+impl IntegratorKind {
+    pub fn integrator(&self) -> &'static dyn Integrator {
+        match self {
+            IntegratorKind::Path => &PathTracer,
+            IntegratorKind::Normals => &Normals,
+        }
+    }
+}
+
+//How multiple importance sampling shares light that both light sampling and scattering
+//can find. Each strategy's weight grows with its density there, so whichever finds the
+//light more reliably carries most of it: light sampling for small emitters, scattering
+//for large ones and glossy surfaces.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum MisHeuristic {
+    Balance, //In proportion to the densities
+    #[default]
+    Power, //To their squares, favouring the better strategy more strongly
+}
+
+impl MisHeuristic {
+    /// Weight of a sample drawn with density `pdf` that the other strategy draws with
+    /// density `other`.
+    pub fn weight(&self, pdf: f64, other: f64) -> f64 {
+        let (f, g) = match self {
+            MisHeuristic::Balance => (pdf, other),
+            MisHeuristic::Power => (pdf * pdf, other * other),
+        };
+        match f + g > 0.0 {
+            true => f / (f + g),
+            false => 0.0,
+        }
+    }
+}
+
+//Unidirectional path tracing with next event estimation, walking the path in a loop so
+//its length is bounded by max_depth rather than the worker threads' stacks.
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, camera: &Camera, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> Colour {
+        let mut radiance = Colour::default();
+        //How much of the light found from here on reaches the camera
+        let mut throughput = Colour {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+        let mut ray = Ray {
+            origin: r.origin(),
+            direction: r.direction(),
+            time: r.time(),
+        };
+        //Density with which the last surface picked the ray's direction, when that surface
+        //also sampled the lights directly. Light the ray then finds on a sampled emitter
+        //or the environment light could have come from either strategy, so it is weighted
+        //by the heuristic against the density light sampling gives it. Camera rays and
+        //specular bounces have None and count everything they find.
+        let mut scatter_pdf: Option<f64> = None;
+
+        for bounce in 0..camera.max_depth {
+            let mut rec: Option<HitRecord> = Some(HitRecord::default());
+            let hit = world.hit(
+                &ray,
+                Interval {
+                    min: 0.001,
+                    max: UNIVERSE_INTERVAL.max,
+                },
+                &mut rec,
+            );
+            let rec = match (hit, rec) {
+                (true, Some(rec)) => rec,
+                _ => {
+                    let mut background = camera.background.value(&ray.direction);
+                    if let (Background::EnvironmentLight(light), Some(pdf)) = (&camera.background, scatter_pdf) {
+                        background = camera.mis_heuristic.weight(pdf, light.pdf(&ray.direction.unit())) * background;
+                    }
+                    radiance += throughput.element_wise_multiply(&background);
+                    break;
+                }
+            };
+
+            let mut emitted = rec.mat_type.emitted(rec.u, rec.v, &rec.p);
+            if let Some(pdf) = scatter_pdf {
+                if rec.area_pdf > 0.0 && !lights.is_empty() {
+                    // Turn the density per area into one per solid angle at the origin
+                    let distance_sq = (rec.p - ray.origin).d_euclidsq();
+                    let cosine = (ray.direction.unit() * rec.n).abs();
+                    let light_pdf = rec.area_pdf * distance_sq / (cosine * lights.len() as f64);
+                    emitted = camera.mis_heuristic.weight(pdf, light_pdf) * emitted;
+                }
+            }
+            radiance += throughput.element_wise_multiply(&emitted);
+
+            let mut srec = ScatterRecord::default();
+            if !rec.mat_type.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+            scatter_pdf = match srec.is_specular {
+                true => None,
+                false => {
+                    let direct = direct_light(camera, &ray, &rec, world, lights);
+                    radiance += throughput.element_wise_multiply(&direct);
+                    Some(srec.pdf)
+                }
+            };
+
+            // Past the minimum bounces a path carries on with a chance that follows its
+            // throughput, and survivors are scaled up by the inverse so nothing is lost
+            // on average. Dim paths end early, bright ones are left to max_depth.
+            throughput = throughput.element_wise_multiply(&srec.attenuation);
+            if bounce >= camera.min_bounces {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+                if survival < 1.0 && rand0_1() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = srec.ray;
+        }
+        radiance
+    }
+}
+
+//Light a surface reflects from one sample of a randomly chosen light and one of the
+//environment light, each weighted against the chance of scattering finding it too.
+//Choosing one light out of n is made up for by counting it n times.
+fn direct_light(camera: &Camera, r_in: &Ray, rec: &HitRecord, world: &dyn Hittable, lights: &[Light]) -> Colour {
+    let mut samples = Vec::with_capacity(2);
+    if !lights.is_empty() {
+        let index = ((rand0_1() * lights.len() as f64) as usize).min(lights.len() - 1);
+        if let Some(mut sample) = lights[index].sample(&rec.p) {
+            sample.pdf /= lights.len() as f64;
+            samples.push(sample);
+        }
+    }
+    if let Background::EnvironmentLight(light) = &camera.background {
+        let (direction, radiance, pdf) = light.sample();
+        samples.push(LightSample {
+            direction,
+            distance: UNIVERSE_INTERVAL.max,
+            radiance,
+            pdf,
+            is_delta: false,
+        });
+    }
+
+    let mut total = Colour::default();
+    for sample in samples {
+        let bsdf_cos = rec.mat_type.eval(r_in, rec, &sample.direction);
+        if sample.pdf <= 0.0 || bsdf_cos.near_zero() {
+            continue;
+        }
+        let shadow = Ray {
+            origin: rec.p,
+            direction: sample.direction,
+            time: r_in.time(),
+        };
+        // Stop just short of the light so it does not shadow itself
+        let unblocked = Interval {
+            min: 0.001,
+            max: sample.distance - 0.001,
+        };
+        let mut blocker: Option<HitRecord> = Some(HitRecord::default());
+        if !world.hit(&shadow, unblocked, &mut blocker) {
+            let weight = match sample.is_delta {
+                true => 1.0,
+                false => camera
+                    .mis_heuristic
+                    .weight(sample.pdf, rec.mat_type.pdf(r_in, rec, &sample.direction)),
+            };
+            total += (weight / sample.pdf) * bsdf_cos.element_wise_multiply(&sample.radiance);
+        }
+    }
+    total
+}
+
+//Shows the outward shading normal of the first surface each ray hits, mapped from
+//[-1, 1] to [0, 1] per axis, and black where rays miss. For checking geometry, smooth
+//normals and which way surfaces face.
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(&self, _camera: &Camera, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Colour {
+        let mut rec: Option<HitRecord> = Some(HitRecord::default());
+        let hit = world.hit(
+            r,
+            Interval {
+                min: 0.001,
+                max: UNIVERSE_INTERVAL.max,
+            },
+            &mut rec,
+        );
+        match (hit, rec) {
+            (true, Some(rec)) => {
+                let outward = match rec.front_face {
+                    true => rec.n,
+                    false => -rec.n,
+                };
+                0.5 * (outward
+                    + Colour {
+                        x: 1.0,
+                        y: 1.0,
+                        z: 1.0,
+                    })
+            }
+            _ => Colour::default(),
+        }
+    }
+}
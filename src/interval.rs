@@ -16,16 +16,34 @@ impl Interval {
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Self {
+            min: self.min - padding,
+            max: self.max + padding,
+        }
+    }
+
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        //Tightest interval containing both a and b
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
     pub fn clamp(&self, x: f64) -> f64 {
-        let retval: f64;
-        retval = if x < self.min {
+        if x < self.min {
             self.min
         } else if x > self.max {
             self.max
         } else {
             x
-        };
-        retval
+        }
     }
 }
 #[allow(dead_code)]
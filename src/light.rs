@@ -0,0 +1,258 @@
+use crate::colour::Colour;
+use crate::hittable::sphere_uv;
+use crate::math::{Mat4, Normal3, Onb, Point3};
+use crate::rtweekend::rand0_1;
+use crate::texture::Texture;
+use crate::vec3::{Vec3, VectorProperties};
+use std::f64::consts::PI;
+
+//Lights that can be sampled directly, so the renderer can aim shadow rays at them instead
+//of waiting for bounces to find them. Every primitive with a DiffuseLight material hands
+//an Area light to the HittableList it is pushed into; its points are picked uniformly by
+//area, which the primitives' hit records mirror in their area_pdf. Point, spot and
+//directional lights have no geometry at all and come from the scene's [[lights]], so
+//shadow rays are the only way they are ever seen.
+
+#[derive(Clone)]
+pub enum LightShape {
+    Quad { q: Vec3, u: Vec3, v: Vec3 },
+    Sphere { centre: Vec3, radius: f64 },
+    Disk { centre: Vec3, normal: Vec3, radius: f64, tangent: Vec3, bitangent: Vec3 },
+    Triangle { a: Vec3, b: Vec3, c: Vec3, uvs: [(f64, f64); 3] }, //Texture coordinates of the corners
+}
+
+#[derive(Clone)]
+pub enum Light {
+    Area {
+        shape: LightShape,
+        emit: Texture,
+        transform: Option<Box<Mat4>>, //Placement of the shape, from Transform objects
+    },
+    Point {
+        position: Vec3,
+        intensity: Colour, //Radiance times area, so what arrives is intensity / distance^falloff
+        falloff: f64,      //Power of the distance, 2 being physical
+    },
+    //A point light shining down a cone, fading smoothly from full strength inside the
+    //inner angle to nothing outside the outer one. Angles are in degrees from the axis.
+    Spot {
+        position: Vec3,
+        direction: Vec3, //Where it shines
+        intensity: Colour,
+        falloff: f64,
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    //Light from very far away such as the sun, arriving in parallel when the angular
+    //diameter (degrees) is 0 and from a small disk of the sky otherwise, which softens
+    //shadows. Irradiance is what a surface facing it receives.
+    Directional {
+        direction: Vec3, //Towards the light
+        irradiance: Colour,
+        angular_diameter: f64,
+    },
+}
+
+/// Light arriving at a point from one sampled point on a light.
+pub struct LightSample {
+    pub(crate) direction: Vec3, //Unit vector towards the light
+    pub(crate) distance: f64,
+    pub(crate) radiance: Colour,
+    pub(crate) pdf: f64, //Over solid angle at the receiving point
+    pub(crate) is_delta: bool, //No scattered ray can hit the light, so nothing else finds it
+}
+
+impl Light {
+    /// An area light over `shape`.
+    pub fn new(shape: LightShape, emit: Texture) -> Self {
+        Light::Area {
+            shape,
+            emit,
+            transform: None,
+        }
+    }
+
+    /// The same light placed by `matrix`, on top of any placement it already has.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let mut light = self.clone();
+        match &mut light {
+            Light::Area { transform, .. } => {
+                *transform = Some(Box::new(match transform {
+                    Some(inner) => *matrix * **inner,
+                    None => *matrix,
+                }))
+            }
+            Light::Point { position, .. } => *position = (*matrix * Point3(*position)).0,
+            Light::Spot { position, direction, .. } => {
+                *position = (*matrix * Point3(*position)).0;
+                *direction = *matrix * *direction;
+            }
+            Light::Directional { direction, .. } => *direction = *matrix * *direction,
+        }
+        light
+    }
+
+    /// A uniformly chosen point of an area light's shape with its outward normal,
+    /// texture coordinates and density per unit area.
+    fn sample_point(shape: &LightShape, transform: &Option<Box<Mat4>>) -> (Vec3, Vec3, (f64, f64), f64) {
+        let (p, n, uv, pdf) = match shape {
+            LightShape::Quad { q, u, v } => {
+                let (alpha, beta) = (rand0_1(), rand0_1());
+                let n = u.cross(v);
+                (*q + alpha * *u + beta * *v, n.unit(), (alpha, beta), 1.0 / n.d_euclid())
+            }
+            LightShape::Sphere { centre, radius } => {
+                let n = Vec3::random_unit_vector();
+                let p = *centre + *radius * n;
+                let pdf = 1.0 / (4.0 * PI * radius * radius);
+                (p, n, sphere_uv(&((p - *centre) / radius.abs())), pdf)
+            }
+            LightShape::Disk {
+                centre,
+                normal,
+                radius,
+                tangent,
+                bitangent,
+            } => {
+                // sqrt keeps the points uniform over the area rather than the radius
+                let (r, phi) = (rand0_1().sqrt(), 2.0 * PI * rand0_1());
+                let offset = *radius * r * (phi.cos() * *tangent + phi.sin() * *bitangent);
+                // Same polar coordinates as Disk::hit
+                let u = (offset * *bitangent).atan2(offset * *tangent) / (2.0 * PI) + 0.5;
+                (*centre + offset, *normal, (u, r), 1.0 / (PI * radius * radius))
+            }
+            LightShape::Triangle { a, b, c, uvs } => {
+                let (mut b1, mut b2) = (rand0_1(), rand0_1());
+                if b1 + b2 > 1.0 {
+                    (b1, b2) = (1.0 - b1, 1.0 - b2);
+                }
+                let b0 = 1.0 - b1 - b2;
+                let n = (*b - *a).cross(&(*c - *a));
+                let uv = (
+                    b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0,
+                    b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1,
+                );
+                (b0 * *a + b1 * *b + b2 * *c, n.unit(), uv, 2.0 / n.d_euclid())
+            }
+        };
+        match transform {
+            None => (p, n, uv, pdf),
+            Some(matrix) => {
+                // Area stretches by |det| times the length of the transformed normal
+                let normal_matrix = matrix.normal_matrix().unwrap_or_default();
+                let n = (normal_matrix * Normal3(n)).0;
+                let scale = matrix.upper_left().determinant().abs() * n.d_euclid();
+                ((**matrix * Point3(p)).0, n.unit(), uv, pdf / scale)
+            }
+        }
+    }
+
+    /// Samples the light as seen from `from`. None when it sends nothing there, such as
+    /// an area light seen exactly edge on or a point outside a spot's cone.
+    pub fn sample(&self, from: &Vec3) -> Option<LightSample> {
+        match self {
+            Light::Area { shape, emit, transform } => {
+                let (p, n, (u, v), area_pdf) = Self::sample_point(shape, transform);
+                let offset = p - *from;
+                let distance_sq = offset.d_euclidsq();
+                let distance = distance_sq.sqrt();
+                let direction = offset / distance;
+                // Emission is two-sided, so either face of the light counts
+                let cosine = (direction * n).abs();
+                if cosine < 1e-8 || !area_pdf.is_finite() || distance_sq == 0.0 {
+                    return None;
+                }
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: emit.value(u, v, &p),
+                    pdf: area_pdf * distance_sq / cosine,
+                    is_delta: false,
+                })
+            }
+            Light::Point {
+                position,
+                intensity,
+                falloff,
+            } => point_sample(from, position, intensity, *falloff),
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                falloff,
+                inner_angle,
+                outer_angle,
+            } => {
+                let mut sample = point_sample(from, position, intensity, *falloff)?;
+                let cosine = -(sample.direction * direction.unit());
+                let (cos_inner, cos_outer) = (inner_angle.to_radians().cos(), outer_angle.to_radians().cos());
+                let strength = match (cos_inner > cos_outer, cosine >= cos_outer) {
+                    (true, _) => smoothstep(((cosine - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0)),
+                    (false, true) => 1.0, //A hard edged cone
+                    (false, false) => 0.0,
+                };
+                if strength <= 0.0 {
+                    return None;
+                }
+                sample.radiance = strength * sample.radiance;
+                Some(sample)
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => {
+                let towards = direction.unit();
+                let cos_max = (angular_diameter.to_radians() / 2.0).cos();
+                if cos_max >= 1.0 {
+                    return Some(LightSample {
+                        direction: towards,
+                        distance: f64::INFINITY,
+                        radiance: *irradiance,
+                        pdf: 1.0,
+                        is_delta: true,
+                    });
+                }
+                // Uniform over the cone of directions to the disk, whose radiance is
+                // the irradiance spread over its solid angle
+                let solid_angle = 2.0 * PI * (1.0 - cos_max);
+                let cos_theta = 1.0 - rand0_1() * (1.0 - cos_max);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * rand0_1();
+                let local = Vec3 {
+                    x: phi.cos() * sin_theta,
+                    y: phi.sin() * sin_theta,
+                    z: cos_theta,
+                };
+                Some(LightSample {
+                    direction: Onb::from_w(&towards).world(&local),
+                    distance: f64::INFINITY,
+                    radiance: *irradiance / solid_angle,
+                    pdf: 1.0 / solid_angle,
+                    is_delta: true,
+                })
+            }
+        }
+    }
+}
+
+//All of a point light's intensity arrives from its one direction, so the sample has a
+//density of 1 and carries the irradiance it gives
+fn point_sample(from: &Vec3, position: &Vec3, intensity: &Colour, falloff: f64) -> Option<LightSample> {
+    let offset = *position - *from;
+    let distance = offset.d_euclid();
+    if distance == 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        direction: offset / distance,
+        distance,
+        radiance: *intensity / distance.powf(falloff),
+        pdf: 1.0,
+        is_delta: true,
+    })
+}
+
+fn smoothstep(x: f64) -> f64 {
+    x * x * (3.0 - 2.0 * x)
+}
@@ -1,46 +1,151 @@
-use std::{f64::consts::PI, sync::Arc};
+use std::{process, sync::Arc};
 
+use bvh::BvhNode;
 use hittable::{HittableList, Sphere};
 use material::Material;
+use texture::Texture;
 
 mod colour;
 use crate::vec3::Vec3;
+mod aabb;
+mod background;
+mod bvh;
 mod camera;
+mod cli;
+mod distribution;
+mod exr;
+mod framebuffer;
+mod gltf_scene;
+mod hdr;
 mod hittable;
+mod integrator;
 mod interval;
+mod light;
 mod material;
+mod math;
+mod noise;
+mod mesh;
+mod obj;
+mod output;
+mod planar;
+mod ply;
+mod postprocess;
 mod ray;
 mod rtweekend;
+mod scene;
+mod stl;
+mod texture;
+mod transform;
+mod validate;
 mod vec3;
 use camera::{Camera, CameraProperties};
+use clap::Parser;
+use cli::{Cli, IntegratorArg, ToneMapArg};
+use integrator::IntegratorKind;
+use output::ImageFormat;
+use postprocess::PostProcess;
 
 fn main() {
+    let cli = Cli::parse();
+    let format = match &cli.format {
+        Some(name) => ImageFormat::from_name(name),
+        None => ImageFormat::from_path(&cli.output),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Global thread pool is only built once");
+    }
+
+    let (world, mut camera) = match &cli.scene {
+        Some(path) => scene::load_scene(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        }),
+        None => demo_scene(),
+    };
+    camera.aspect_ratio = cli.aspect_ratio.unwrap_or(camera.aspect_ratio);
+    camera.image_width = cli.width.unwrap_or(camera.image_width);
+    camera.samples_per_pixel = cli.samples.unwrap_or(camera.samples_per_pixel);
+    camera.max_depth = cli.max_depth.unwrap_or(camera.max_depth);
+    camera.min_bounces = cli.min_bounces.unwrap_or(camera.min_bounces);
+    camera.integrator = match cli.integrator {
+        Some(IntegratorArg::Path) => IntegratorKind::Path,
+        Some(IntegratorArg::Normals) => IntegratorKind::Normals,
+        None => camera.integrator,
+    };
+    camera.vfov = cli.vfov.unwrap_or(camera.vfov);
+    camera.lookfrom = cli.lookfrom.unwrap_or(camera.lookfrom);
+    camera.lookat = cli.lookat.unwrap_or(camera.lookat);
+    camera.vup = cli.vup.unwrap_or(camera.vup);
+    camera.defocus_angle = cli.defocus_angle.unwrap_or(camera.defocus_angle);
+    camera.focus_dist = cli.focus_dist.or(camera.focus_dist);
+    camera.shutter_open = cli.shutter_open.unwrap_or(camera.shutter_open);
+    camera.shutter_close = cli.shutter_close.unwrap_or(camera.shutter_close);
+    camera.seed = cli.seed;
+    camera.initialize();
+
+    let report = validate::validate_scene(&world, &camera);
+    for diagnostic in &report.diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if report.has_errors() {
+        process::exit(1);
+    }
+
+    let lights = world.lights.clone();
+    let world = BvhNode::new(world);
+    let mut image = camera.render(&world, &lights);
+    let samples: u64 = image.samples().iter().map(|&n| n as u64).sum();
+    eprintln!("\rDone, {} samples.", samples);
+    if let Some(max) = cli.clamp {
+        PostProcess::Clamp(max).apply(&mut image);
+    }
+    if let Some(stops) = cli.exposure {
+        PostProcess::Exposure(stops).apply(&mut image);
+    }
+    match cli.tonemap {
+        ToneMapArg::None => {}
+        ToneMapArg::Reinhard => PostProcess::Reinhard.apply(&mut image),
+        ToneMapArg::Aces => PostProcess::Aces.apply(&mut image),
+    }
+    if let Err(err) = output::write_image(&cli.output, format, &image) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn demo_scene() -> (HittableList, Camera) {
     let material_ground = Material::Lambertian {
-        albedo: Vec3 {
+        albedo: Texture::Solid(Vec3 {
             x: (0.8),
             y: (0.8),
-            z: (8.0),
-        },
+            z: (0.0),
+        }),
     };
     let material_centre = Material::Lambertian {
-        albedo: Vec3 {
+        albedo: Texture::Solid(Vec3 {
             x: (0.1),
             y: (0.2),
             z: (0.5),
-        },
+        }),
     };
     let material_left = Material::Dielectric { idx_refract: (1.5) };
     let materia_right = Material::Metal {
-        albedo: Vec3 {
+        albedo: Texture::Solid(Vec3 {
             x: 0.8,
             y: 0.6,
             z: 0.2,
-        },
+        }),
         fuzz: (0.0),
     };
 
     //let material_dia = Material::Dielectric { idx_refract: (1.5) };
-    let R = (PI / 4.0).cos();
     let camera = Camera::new(1.0, 800, 10);
     let mut world = HittableList::new();
     world.push(Arc::new(Sphere {
@@ -68,7 +173,7 @@ fn main() {
             z: -1.0,
         },
         radius: 0.5,
-        mat_type: material_left,
+        mat_type: material_left.clone(),
     })); //left1 sphere dielectric
     world.push(Arc::new(Sphere {
         centre: Vec3 {
@@ -86,22 +191,8 @@ fn main() {
             z: -1.0,
         },
         radius: 0.5,
-        mat_type: material_left,
+        mat_type: materia_right,
     })); //right sphere metal
 
-    let cam_set = Camera {
-        lookfrom: Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        },
-        lookat: Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        vup:Vec3{ x: 0.0, y: 1.0, z: 0.0},
-        ..camera
-    };
-    cam_set.render(&world);
+    (world, camera)
 }
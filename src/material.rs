@@ -1,22 +1,35 @@
 use crate::colour;
 use crate::hittable::HitRecord;
-use crate::ray::Ray;
+use crate::math::Onb;
+use crate::ray::{Ray, RayProperties};
 use crate::rtweekend::rand0_1;
+use crate::texture::Texture;
 use crate::vec3::Vec3;
 use crate::vec3::VectorProperties;
 use colour::Colour;
+use std::f64::consts::PI;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Material {
-    Lambertian { albedo: Colour },
-    Default { albedo: Colour },
-    Metal { albedo: Colour, fuzz: f64 }, // Add more material types as needed
+    Lambertian { albedo: Texture },
+    Default { albedo: Texture },
+    Metal { albedo: Texture, fuzz: f64 }, // Add more material types as needed
     Dielectric { idx_refract: f64 },
+    DiffuseLight { emit: Texture }, //Emits from both sides and scatters nothing
 }
+/// How a material scattered an incoming ray.
+#[derive(Default)]
+pub struct ScatterRecord {
+    pub(crate) attenuation: Colour, //BSDF times cosine over pdf, what the scattered light is scaled by
+    pub(crate) ray: Ray,
+    pub(crate) pdf: f64, //Of the ray's direction over solid angle, unused when specular
+    pub(crate) is_specular: bool, //A delta lobe such as a mirror, which eval and pdf never see
+}
+
 impl Default for Material {
     fn default() -> Self {
         Material::Default {
-            albedo: Colour::default(),
+            albedo: Texture::default(),
         }
     }
 }
@@ -27,53 +40,121 @@ impl Material {
         rtheta + (1.0 - rtheta) * (1.0 - cosine).powi(5)
         //Reflectivity Schlick approximation
     }
-    pub fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
-    ) -> bool {
+
+    //1 - cos of the half angle of fuzzy metal's cone. It reaches asin(fuzz) from the mirror
+    //direction, as far as offsetting it by a sphere of radius fuzz would, and covers the
+    //whole hemisphere from fuzz 1 up.
+    fn metal_cone(fuzz: f64) -> f64 {
+        let fuzz_sq = (fuzz * fuzz).min(1.0);
+        fuzz_sq / (1.0 + (1.0 - fuzz_sq).sqrt())
+    }
+
+    /// The BSDF times the cosine of `direction` with the normal, for light arriving from
+    /// `direction` and leaving along `r_in` reversed. Zero for mirrors and glass, which
+    /// only scatter can sample.
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Colour {
         match self {
-            Material::Default { albedo } => {
-                let mut scatter_direction = rec.n + Vec3::random_unit_vector();
-                if scatter_direction.near_zero() {
-                    scatter_direction = rec.n;
+            Material::Default { albedo } | Material::Lambertian { albedo } => {
+                let cosine = (direction.unit() * rec.n).max(0.0);
+                (cosine / PI) * albedo.value(rec.u, rec.v, &rec.p)
+            }
+            //Chosen so that scatter's weight, eval over pdf, is just the albedo
+            Material::Metal { albedo, .. } => match *direction * rec.n > 0.0 {
+                true => self.pdf(r_in, rec, direction) * albedo.value(rec.u, rec.v, &rec.p),
+                false => Colour::default(),
+            },
+            _ => Colour::default(),
+        }
+    }
+
+    /// Density over solid angle with which scatter picks `direction`, zero where eval is.
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        match self {
+            Material::Default { .. } | Material::Lambertian { .. } => (direction.unit() * rec.n).max(0.0) / PI,
+            Material::Metal { fuzz, .. } if *fuzz != 0.0 => {
+                let reflected = Vec3::reflect(&r_in.direction.unit(), &rec.n);
+                let cone = Self::metal_cone(*fuzz);
+                match 1.0 - direction.unit() * reflected <= cone {
+                    true => 1.0 / (2.0 * PI * cone),
+                    false => 0.0,
                 }
-                *scattered = Ray {
-                    origin: rec.p,
-                    direction: scatter_direction,
-                };
-                *attenuation = *albedo;
-                true
-            } // Default has the same implementation as Lambertian
-            Material::Lambertian { albedo } => {
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Colour {
+        match self {
+            Material::DiffuseLight { emit } => emit.value(u, v, p),
+            _ => Colour::default(),
+        }
+    }
+
+    /// Density of a hit point when the light list samples a surface of this area,
+    /// 0 for materials that are never sampled. See Hittable::lights.
+    pub fn light_area_pdf(&self, area: f64) -> f64 {
+        match self {
+            Material::DiffuseLight { .. } => 1.0 / area,
+            _ => 0.0,
+        }
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        match self {
+            Material::Default { albedo } | Material::Lambertian { albedo } => {
+                //Cosine weighted, so the attenuation is just the albedo
                 let mut scatter_direction = rec.n + Vec3::random_unit_vector();
                 if scatter_direction.near_zero() {
                     scatter_direction = rec.n;
                 }
-                *scattered = Ray {
+                srec.pdf = self.pdf(r_in, rec, &scatter_direction);
+                srec.ray = Ray {
                     origin: rec.p,
                     direction: scatter_direction,
+                    time: r_in.time(),
                 };
-                *attenuation = *albedo;
+                srec.attenuation = albedo.value(rec.u, rec.v, &rec.p);
+                srec.is_specular = false;
                 true
             }
             Material::Metal { albedo, fuzz } => {
                 let reflected = Vec3::reflect(&r_in.direction.unit(), &rec.n);
-                *scattered = Ray {
+                srec.is_specular = *fuzz == 0.0;
+                let direction = match srec.is_specular {
+                    true => reflected,
+                    false => {
+                        //Uniform over the cone, whose cosines are spread evenly down to 1 - cone
+                        let cos_theta = 1.0 - rand0_1() * Self::metal_cone(*fuzz);
+                        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                        let phi = 2.0 * PI * rand0_1();
+                        let local = Vec3 {
+                            x: phi.cos() * sin_theta,
+                            y: phi.sin() * sin_theta,
+                            z: cos_theta,
+                        };
+                        Onb::from_w(&reflected).world(&local)
+                    }
+                };
+                // Reflections that would go into the surface are absorbed
+                if direction * rec.n <= 0.0 {
+                    return false;
+                }
+                srec.pdf = self.pdf(r_in, rec, &direction);
+                srec.ray = Ray {
                     origin: rec.p,
-                    direction: reflected + *fuzz * Vec3::random_unit_vector(),
+                    direction,
+                    time: r_in.time(),
                 };
-                *attenuation = *albedo;
+                srec.attenuation = albedo.value(rec.u, rec.v, &rec.p);
                 true
             }
             Material::Dielectric { idx_refract } => {
-                *attenuation = Colour {
+                srec.attenuation = Colour {
                     x: 1.0,
                     y: 1.0,
                     z: 1.0,
                 };
+                srec.is_specular = true;
                 let refraction_ratio = match rec.front_face {
                     true => 1.0 / idx_refract,
                     false => *idx_refract,
@@ -88,13 +169,94 @@ impl Material {
                 };
 
                 //let refracted = Vec3::refract(&unit_direction, &rec.n,refraction_ratio);
-                *scattered = Ray {
+                srec.ray = Ray {
                     origin: rec.p,
                     direction: refracted,
+                    time: r_in.time(),
                 };
 
                 true
             }
+            Material::DiffuseLight { .. } => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_metal_scatters_with_its_own_density() {
+        let albedo = Colour { x: 0.9, y: 0.6, z: 0.3 };
+        let metal = Material::Metal {
+            albedo: Texture::Solid(albedo),
+            fuzz: 0.4,
+        };
+        let rec = HitRecord {
+            n: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            front_face: true,
+            ..Default::default()
+        };
+        let r_in = Ray {
+            origin: Vec3 { x: -1.0, y: 1.0, z: 0.0 },
+            direction: Vec3 { x: 1.0, y: -1.0, z: 0.0 },
+            time: 0.0,
+        };
+
+        // The density integrates to one over the sphere of directions
+        let n = 400;
+        let mut total = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let cos_theta = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                let direction = Vec3 {
+                    x: phi.cos() * sin_theta,
+                    y: cos_theta,
+                    z: phi.sin() * sin_theta,
+                };
+                total += metal.pdf(&r_in, &rec, &direction) * (2.0 / n as f64) * (2.0 * PI / n as f64);
+            }
+        }
+        assert!((total - 1.0).abs() < 0.02, "{}", total);
+
+        for _ in 0..1000 {
+            let mut srec = ScatterRecord::default();
+            if !metal.scatter(&r_in, &rec, &mut srec) {
+                continue;
+            }
+            assert!(!srec.is_specular);
+            assert!(srec.pdf > 0.0);
+            assert_eq!(srec.pdf, metal.pdf(&r_in, &rec, &srec.ray.direction));
+            // What scatter weights by is what eval and pdf give for its direction
+            let weight = metal.eval(&r_in, &rec, &srec.ray.direction) / srec.pdf;
+            assert!((weight - srec.attenuation).near_zero());
+            assert!((srec.attenuation - albedo).near_zero());
+        }
+    }
+
+    #[test]
+    fn only_unfuzzed_metal_is_a_mirror() {
+        let rec = HitRecord {
+            n: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            front_face: true,
+            ..Default::default()
+        };
+        let r_in = Ray {
+            origin: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            direction: Vec3 { x: 0.0, y: -1.0, z: 0.0 },
+            time: 0.0,
+        };
+        let mirror = Material::Metal {
+            albedo: Texture::default(),
+            fuzz: 0.0,
+        };
+        let mut srec = ScatterRecord::default();
+        assert!(mirror.scatter(&r_in, &rec, &mut srec));
+        assert!(srec.is_specular);
+        assert_eq!(mirror.pdf(&r_in, &rec, &rec.n), 0.0);
+        assert!(mirror.eval(&r_in, &rec, &rec.n).near_zero());
+    }
+}
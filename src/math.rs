@@ -0,0 +1,464 @@
+pub use crate::transform::{Mat4, IDENTITY};
+use crate::vec3::{Vec3, VectorProperties};
+use std::ops;
+
+//Linear algebra on top of Vec3: matrices, quaternions, orthonormal bases, and
+//Point3/Normal3 wrappers so positions and normals get the transform they need.
+//Vec3 itself stays the type for directions, offsets and colours.
+
+/// A position. Translations move it, unlike a Vec3.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Point3(pub(crate) Vec3);
+
+/// A surface normal. Transformed by the inverse transpose so it stays
+/// perpendicular to the surface under non-uniform scaling.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Normal3(pub(crate) Vec3);
+
+impl From<Vec3> for Point3 {
+    fn from(v: Vec3) -> Self {
+        Point3(v)
+    }
+}
+
+impl From<Point3> for Vec3 {
+    fn from(p: Point3) -> Self {
+        p.0
+    }
+}
+
+impl From<Vec3> for Normal3 {
+    fn from(v: Vec3) -> Self {
+        Normal3(v)
+    }
+}
+
+impl From<Normal3> for Vec3 {
+    fn from(n: Normal3) -> Self {
+        n.0
+    }
+}
+
+impl ops::Sub<Point3> for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Point3) -> Vec3 {
+        self.0 - rhs.0
+    }
+}
+
+impl ops::Add<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, rhs: Vec3) -> Point3 {
+        Point3(self.0 + rhs)
+    }
+}
+
+impl ops::Sub<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, rhs: Vec3) -> Point3 {
+        Point3(self.0 - rhs)
+    }
+}
+
+impl Normal3 {
+    pub fn unit(&self) -> Normal3 {
+        Normal3(self.0.unit())
+    }
+}
+
+impl ops::Neg for Normal3 {
+    type Output = Normal3;
+
+    fn neg(self) -> Normal3 {
+        Normal3(-self.0)
+    }
+}
+
+//3x3 matrix acting on column vectors, m[row][column]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat3 {
+    pub(crate) m: [[f64; 3]; 3],
+}
+
+pub const MAT3_IDENTITY: Mat3 = Mat3 {
+    m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+};
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        MAT3_IDENTITY
+    }
+}
+
+impl Mat3 {
+    pub fn column(&self, c: usize) -> Vec3 {
+        Vec3 {
+            x: self.m[0][c],
+            y: self.m[1][c],
+            z: self.m[2][c],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut out = MAT3_IDENTITY;
+        for (r, row) in out.m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = self.m[c][r];
+            }
+        }
+        out
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.column(0) * self.column(1).cross(&self.column(2))
+    }
+
+    /// None for a singular matrix.
+    pub fn inverse(&self) -> Option<Self> {
+        //The rows of the inverse are the cross products of pairs of columns over the determinant
+        let (a, b, c) = (self.column(0), self.column(1), self.column(2));
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let rows = [b.cross(&c) / det, c.cross(&a) / det, a.cross(&b) / det];
+        Some(Mat3 {
+            m: rows.map(|r| [r.x, r.y, r.z]),
+        })
+    }
+}
+
+impl ops::Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut out = MAT3_IDENTITY;
+        for (r, row) in out.m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[r][k] * rhs.m[k][c]).sum();
+            }
+        }
+        out
+    }
+}
+
+impl ops::Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+//Expects a normal matrix (see Mat4::normal_matrix), the result is not renormalised
+impl ops::Mul<Normal3> for Mat3 {
+    type Output = Normal3;
+
+    fn mul(self, n: Normal3) -> Normal3 {
+        Normal3(self * n.0)
+    }
+}
+
+//The affine matrices of transform.rs, seen through points, normals and quaternions
+impl Mat4 {
+    pub fn upper_left(&self) -> Mat3 {
+        let m = &self.m;
+        Mat3 {
+            m: [
+                [m[0][0], m[0][1], m[0][2]],
+                [m[1][0], m[1][1], m[1][2]],
+                [m[2][0], m[2][1], m[2][2]],
+            ],
+        }
+    }
+
+    /// Inverse transpose of the linear part, what normals are multiplied by.
+    /// None for a singular matrix.
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        self.upper_left().inverse().map(|inv| inv.transpose())
+    }
+}
+
+impl ops::Mul<Point3> for Mat4 {
+    type Output = Point3;
+
+    fn mul(self, p: Point3) -> Point3 {
+        Point3(self.transform_point(&p.0))
+    }
+}
+
+//Vectors ignore the translation
+impl ops::Mul<Vec3> for Mat4 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.transform_vector(&v)
+    }
+}
+
+impl From<Quat> for Mat4 {
+    fn from(q: Quat) -> Self {
+        let Quat { w, x, y, z } = q.unit();
+        Mat4 {
+            m: [
+                [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+                [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+                [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+}
+
+/// Rotation quaternion w + xi + yj + zk.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quat {
+    pub(crate) w: f64,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) z: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+}
+
+impl Quat {
+    pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Self {
+        let axis = axis.unit();
+        let (s, c) = (degrees.to_radians() / 2.0).sin_cos();
+        Quat {
+            w: c,
+            x: axis.x * s,
+            y: axis.y * s,
+            z: axis.z * s,
+        }
+    }
+
+    pub fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn unit(&self) -> Quat {
+        let len = self.dot(self).sqrt();
+        Quat {
+            w: self.w / len,
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+        }
+    }
+
+    /// Spherical interpolation along the shorter arc, t = 0 gives a and t = 1 gives b.
+    //Nothing animates rotations yet, so only the tests below call this
+    #[allow(dead_code)]
+    pub fn slerp(a: &Quat, b: &Quat, t: f64) -> Quat {
+        let (a, mut b) = (a.unit(), b.unit());
+        let mut cos = a.dot(&b);
+        if cos < 0.0 {
+            b = Quat {
+                w: -b.w,
+                x: -b.x,
+                y: -b.y,
+                z: -b.z,
+            };
+            cos = -cos;
+        }
+        //Nearly parallel: the sines below vanish, a normalised lerp is just as good
+        let (s0, s1) = match cos > 0.9995 {
+            true => (1.0 - t, t),
+            false => {
+                let theta = cos.acos();
+                let sin = theta.sin();
+                (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+            }
+        };
+        Quat {
+            w: s0 * a.w + s1 * b.w,
+            x: s0 * a.x + s1 * b.x,
+            y: s0 * a.y + s1 * b.y,
+            z: s0 * a.z + s1 * b.z,
+        }
+        .unit()
+    }
+}
+
+impl ops::Mul<Quat> for Quat {
+    type Output = Quat;
+
+    //Hamilton product, `a * b` rotates by b first
+    fn mul(self, rhs: Quat) -> Quat {
+        let (a, b) = (self, rhs);
+        Quat {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+}
+
+/// Orthonormal basis with w along a given direction, for shading frames and
+/// for building tangents on surfaces.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Onb {
+    pub(crate) u: Vec3,
+    pub(crate) v: Vec3,
+    pub(crate) w: Vec3,
+}
+
+impl Onb {
+    pub fn from_w(n: &Vec3) -> Self {
+        //Duff et al. 2017, "Building an Orthonormal Basis, Revisited": continuous
+        //everywhere except across the z = 0 plane's sign flip, with no special cases
+        let w = n.unit();
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        Onb {
+            u: Vec3 {
+                x: 1.0 + sign * w.x * w.x * a,
+                y: sign * b,
+                z: -sign * w.x,
+            },
+            v: Vec3 {
+                x: b,
+                y: sign + w.y * w.y * a,
+                z: -w.y,
+            },
+            w,
+        }
+    }
+
+    //Coordinates in the basis to world space
+    pub fn world(&self, a: &Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).d_euclid() < 1e-9, "{:?} vs {:?}", a, b);
+    }
+
+    fn assert_identity<const N: usize>(m: [[f64; N]; N]) {
+        for (r, row) in m.iter().enumerate() {
+            for (c, value) in row.iter().enumerate() {
+                let expected = if r == c { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-9, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn mat4_inverse_undoes_the_transform() {
+        let m = Mat4::translation(vec(1.0, -2.0, 3.0))
+            * Mat4::rotation(vec(1.0, 1.0, 0.0), 35.0)
+            * Mat4::scaling(vec(2.0, 0.5, 3.0));
+        let inverse = m.inverse().unwrap();
+        assert_identity((m * inverse).m);
+        assert_identity((inverse * m).m);
+        let p = vec(0.3, -4.0, 2.5);
+        assert_near((inverse * (m * Point3(p))).0, p);
+        assert!(Mat4::scaling(vec(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn mat3_inverse_and_determinant() {
+        let m = Mat3 {
+            m: [[2.0, 1.0, 0.0], [0.0, 3.0, 1.0], [1.0, 0.0, 4.0]],
+        };
+        assert!((m.determinant() - 25.0).abs() < 1e-12);
+        assert_identity((m * m.inverse().unwrap()).m);
+        let singular = Mat3 {
+            m: [[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]],
+        };
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let m = Mat4::rotation(vec(0.0, 0.0, 1.0), 30.0) * Mat4::scaling(vec(4.0, 1.0, 1.0));
+        //A surface containing the tangent, with the normal perpendicular to it
+        let (tangent, normal) = (vec(1.0, 1.0, 0.0), vec(1.0, -1.0, 0.0));
+        let n = m.normal_matrix().unwrap() * Normal3(normal);
+        assert!((m * tangent * n.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rotation_matches_its_quaternion() {
+        let v = vec(1.0, 0.0, 0.0);
+        // Counter-clockwise about +z takes +x to +y
+        assert_near(Mat4::rotation(vec(0.0, 0.0, 1.0), 90.0) * v, vec(0.0, 1.0, 0.0));
+        // Quaternion products compose like the matrices, b first
+        let a = Quat::from_axis_angle(vec(0.0, 0.0, 1.0), 90.0);
+        let b = Quat::from_axis_angle(vec(1.0, 0.0, 0.0), 90.0);
+        let up = vec(0.0, 1.0, 0.0);
+        assert_near(Mat4::from(a * b) * up, Mat4::from(a) * (Mat4::from(b) * up));
+    }
+
+    #[test]
+    fn slerp_interpolates_the_angle() {
+        let axis = vec(0.0, 1.0, 0.0);
+        let (a, b) = (Quat::from_axis_angle(axis, 10.0), Quat::from_axis_angle(axis, 90.0));
+        let halfway = Quat::slerp(&a, &b, 0.5);
+        assert!((halfway.dot(&Quat::from_axis_angle(axis, 50.0)) - 1.0).abs() < 1e-9);
+        assert!((Quat::slerp(&a, &b, 0.0).dot(&a) - 1.0).abs() < 1e-9);
+        assert!((Quat::slerp(&a, &b, 1.0).dot(&b) - 1.0).abs() < 1e-9);
+        // -b is the same rotation, the shorter arc is still taken
+        let negated = Quat {
+            w: -b.w,
+            x: -b.x,
+            y: -b.y,
+            z: -b.z,
+        };
+        assert!((Quat::slerp(&a, &negated, 0.5).dot(&halfway).abs() - 1.0).abs() < 1e-9);
+        // Nearly equal rotations take the normalised lerp path
+        let close = Quat::from_axis_angle(axis, 10.001);
+        assert!((Quat::slerp(&a, &close, 0.5).dot(&a) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn onb_is_orthonormal_around_w() {
+        for n in [
+            vec(0.0, 0.0, 1.0),
+            vec(0.0, 0.0, -1.0),
+            vec(1.0, 0.0, 0.0),
+            vec(0.3, -2.0, 0.5),
+            vec(-1.0, 1.0, -1e-9),
+        ] {
+            let Onb { u, v, w } = Onb::from_w(&n);
+            assert_near(w, n.unit());
+            for (a, b) in [(u, v), (v, w), (w, u)] {
+                assert!((a * b).abs() < 1e-9);
+            }
+            for axis in [u, v] {
+                assert!((axis.d_euclid() - 1.0).abs() < 1e-9);
+            }
+            // Right-handed, so u x v is w
+            assert_near(u.cross(&v), w);
+        }
+    }
+}
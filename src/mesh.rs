@@ -0,0 +1,263 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    colour::Colour,
+    hittable::{HitRecord, Hittable, HittableList},
+    interval::Interval,
+    light::{Light, LightShape},
+    material::Material,
+    obj::load_obj,
+    planar::hit_triangle,
+    ply::load_ply,
+    ray::{Ray, RayProperties},
+    stl::load_stl,
+    texture::Texture,
+    validate::Report,
+    vec3::{Vec3, VectorProperties},
+};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            MeshError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+//Indexed triangle data shared by every triangle of a mesh. Vertex attributes
+//other than positions are optional and, when present, have one entry per position.
+#[derive(Default)]
+pub struct MeshData {
+    pub(crate) positions: Vec<Vec3>,
+    pub(crate) normals: Vec<Vec3>,
+    pub(crate) uvs: Vec<(f64, f64)>,
+    pub(crate) colours: Vec<Colour>, //Multiplies the albedo of Lambertian faces
+    pub(crate) indices: Vec<[u32; 3]>,
+    pub(crate) materials: Vec<Material>,
+    pub(crate) face_materials: Vec<u32>, //Index into materials for each triangle
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+pub struct TriangleMesh {
+    pub(crate) data: Arc<MeshData>,
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData) -> Self {
+        let data = Arc::new(data);
+        let mut triangles = HittableList::new();
+        for face in 0..data.indices.len() {
+            triangles.push(Arc::new(MeshTriangle {
+                mesh: data.clone(),
+                face,
+            }));
+        }
+        TriangleMesh {
+            data,
+            bvh: BvhNode::new(triangles),
+        }
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [usize; 3] {
+        let [a, b, c] = self.mesh.indices[self.face];
+        [a as usize, b as usize, c as usize]
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        let mesh = &self.mesh;
+        let [ia, ib, ic] = self.vertices();
+        let (a, b, c) = (mesh.positions[ia], mesh.positions[ib], mesh.positions[ic]);
+        let (t, b1, b2) = match hit_triangle(&a, &b, &c, r, ray_t) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let b0 = 1.0 - b1 - b2;
+
+        // The geometric normal decides which side was hit, the interpolated
+        // vertex normal (when there is one) is what shading sees.
+        let cross = (b - a).cross(&(c - a));
+        let geometric = cross.unit();
+        let front_face = r.direction() * geometric < 0.0;
+        let mut shading = match mesh.normals.is_empty() {
+            true => geometric,
+            false => (b0 * mesh.normals[ia] + b1 * mesh.normals[ib] + b2 * mesh.normals[ic]).unit(),
+        };
+        if shading * geometric < 0.0 {
+            shading = -shading;
+        }
+        let (u, v) = match mesh.uvs.is_empty() {
+            true => (b1, b2),
+            false => {
+                let (uva, uvb, uvc) = (mesh.uvs[ia], mesh.uvs[ib], mesh.uvs[ic]);
+                (
+                    b0 * uva.0 + b1 * uvb.0 + b2 * uvc.0,
+                    b0 * uva.1 + b1 * uvb.1 + b2 * uvc.1,
+                )
+            }
+        };
+
+        let mut mat_type = mesh.materials[mesh.face_materials[self.face] as usize].clone();
+        let area_pdf = mat_type.light_area_pdf(cross.d_euclid() / 2.0);
+        if let (Material::Lambertian { albedo: Texture::Solid(albedo) }, false) = (&mat_type, mesh.colours.is_empty()) {
+            let colour = b0 * mesh.colours[ia] + b1 * mesh.colours[ib] + b2 * mesh.colours[ic];
+            mat_type = Material::Lambertian {
+                albedo: Texture::Solid(albedo.element_wise_multiply(&colour)),
+            };
+        }
+
+        *rec = Some(HitRecord {
+            p: r.at(t),
+            n: if front_face { shading } else { -shading },
+            mat_type,
+            t,
+            u,
+            v,
+            front_face,
+            area_pdf,
+        });
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [ia, ib, ic] = self.vertices();
+        let p = &self.mesh.positions;
+        Aabb::surrounding(&Aabb::from_points(p[ia], p[ib]), &Aabb::from_points(p[ic], p[ic]))
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        let mesh = &self.mesh;
+        let emit = match &mesh.materials[mesh.face_materials[self.face] as usize] {
+            Material::DiffuseLight { emit } => emit.clone(),
+            _ => return Vec::new(),
+        };
+        let [ia, ib, ic] = self.vertices();
+        let uvs = match mesh.uvs.is_empty() {
+            true => [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            false => [mesh.uvs[ia], mesh.uvs[ib], mesh.uvs[ic]],
+        };
+        let shape = LightShape::Triangle {
+            a: mesh.positions[ia],
+            b: mesh.positions[ib],
+            c: mesh.positions[ic],
+            uvs,
+        };
+        vec![Light::new(shape, emit)]
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        self.bvh.lights()
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let data = &self.data;
+        let context = format!("mesh with {} triangles", data.indices.len());
+        if data.indices.is_empty() {
+            report.warn(format!("{}: mesh is empty", context));
+        }
+        let degenerate = data
+            .indices
+            .iter()
+            .filter(|[a, b, c]| {
+                let (a, b, c) = (
+                    data.positions[*a as usize],
+                    data.positions[*b as usize],
+                    data.positions[*c as usize],
+                );
+                (b - a).cross(&(c - a)).near_zero()
+            })
+            .count();
+        if degenerate > 0 {
+            report.warn(format!("{}: {} triangles have no area and can never be hit", context, degenerate));
+        }
+        for mat in &data.materials {
+            report.check_material(mat, &context);
+        }
+    }
+}
+
+pub fn default_mesh_material(grey: f64) -> Material {
+    Material::Lambertian {
+        albedo: Texture::Solid(Colour {
+            x: grey,
+            y: grey,
+            z: grey,
+        }),
+    }
+}
+
+/// Loads an OBJ, PLY or STL file, picked by extension. `material` is used for
+/// faces the file does not assign a material to; when it is None, vertex coloured
+/// meshes get a white Lambertian so their colours show unchanged and others a grey one.
+pub fn load_mesh(path: &Path, material: Option<Material>) -> Result<MeshData, MeshError> {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let fallback = material.clone().unwrap_or_else(|| default_mesh_material(0.5));
+    let mut data = match ext.as_deref() {
+        Some("obj") => load_obj(path, fallback)?,
+        Some("ply") => load_ply(path, fallback)?,
+        Some("stl") => load_stl(path, fallback)?,
+        _ => {
+            return Err(MeshError::Parse {
+                path: path.to_path_buf(),
+                line: 0,
+                message: "unsupported mesh format, expected .obj, .ply or .stl".to_string(),
+            })
+        }
+    };
+    if material.is_none() && !data.colours.is_empty() {
+        data.materials[0] = default_mesh_material(1.0);
+    }
+    Ok(data)
+}
+
+//The mesh loaders read from disk, so their tests write each input to its own temporary file
+#[cfg(test)]
+pub(crate) fn load_temp<T>(name: &str, contents: &[u8], load: impl FnOnce(&std::path::Path) -> T) -> T {
+    let path = std::env::temp_dir().join(format!("graphics_rust_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    let loaded = load(&path);
+    std::fs::remove_file(path).unwrap();
+    loaded
+}
@@ -0,0 +1,138 @@
+use crate::vec3::{Vec3, VectorProperties};
+use rand::prelude::*;
+
+//Gradient (Perlin) and cellular (Worley) noise for procedural textures. Both are
+//built from a seed of their own so a scene looks the same whatever the render seed.
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3 {
+                    x: rng.gen_range(-1.0..1.0),
+                    y: rng.gen_range(-1.0..1.0),
+                    z: rng.gen_range(-1.0..1.0),
+                }
+                .unit()
+            })
+            .collect();
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        Perlin {
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+            gradients,
+        }
+    }
+
+    /// Smooth noise in about [-1, 1], zero at every lattice point.
+    pub fn noise(&self, p: &Vec3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+        trilinear(&c, u, v, w)
+    }
+
+    /// Fractal sum of |noise| over `depth` octaves, each twice the frequency and
+    /// half the weight of the previous one.
+    pub fn turbulence(&self, p: &Vec3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut point = *p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(&point).abs();
+            weight *= 0.5;
+            point = 2.0 * point;
+        }
+        accum
+    }
+}
+
+fn trilinear(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    //Hermite smoothing hides the lattice, the gradients give each cell its own slope
+    let (uu, vv, ww) = (hermite(u), hermite(v), hermite(w));
+    let mut accum = 0.0;
+    for (i, plane) in c.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let offset = Vec3 {
+                    x: u - fi,
+                    y: v - fj,
+                    z: w - fk,
+                };
+                accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * (*gradient * offset);
+            }
+        }
+    }
+    accum
+}
+
+fn hermite(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Distance from p to the nearest feature point, one feature point per unit cell.
+/// Mostly within [0, 1].
+pub fn worley(p: &Vec3, seed: u64) -> f64 {
+    let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let mut nearest = f64::INFINITY;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let cell = Vec3 {
+                    x: fx + dx as f64,
+                    y: fy + dy as f64,
+                    z: fz + dz as f64,
+                };
+                let feature = cell + cell_offset(cell.x as i64, cell.y as i64, cell.z as i64, seed);
+                nearest = nearest.min((feature - *p).d_euclidsq());
+            }
+        }
+    }
+    nearest.sqrt()
+}
+
+fn cell_offset(x: i64, y: i64, z: i64, seed: u64) -> Vec3 {
+    //splitmix64 over the cell coordinates, three 21-bit fractions from one hash
+    let mut h = seed ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    h ^= (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h ^= (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    let fraction = |shift: u32| ((h >> shift) & 0x1F_FFFF) as f64 / 0x20_0000 as f64;
+    Vec3 {
+        x: fraction(0),
+        y: fraction(21),
+        z: fraction(42),
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    colour::Colour,
+    material::Material,
+    mesh::{MeshData, MeshError},
+    texture::{ImageTexture, Texture, WrapMode},
+    vec3::{Vec3, VectorProperties},
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+//Wavefront OBJ and MTL import. Supports v, vt, vn, polygonal f (fan triangulated,
+//negative indices allowed), mtllib and usemtl. Groups, objects and smoothing groups are
+//ignored: vertex normals in the file are what gives smooth shading.
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> MeshError {
+        MeshError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn floats<const N: usize>(&self, args: &[&str], keyword: &str) -> Result<[f64; N], MeshError> {
+        let mut out = [0.0; N];
+        if args.len() < N {
+            return Err(self.error(format!("'{}' needs {} numbers, found {}", keyword, N, args.len())));
+        }
+        for (value, arg) in out.iter_mut().zip(args) {
+            *value = arg
+                .parse()
+                .map_err(|_| self.error(format!("'{}' is not a number", arg)))?;
+        }
+        Ok(out)
+    }
+
+    fn index(&self, token: &str, count: usize, what: &str) -> Result<usize, MeshError> {
+        //OBJ indices are 1-based, negative ones count back from the latest element
+        let raw: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("bad {} index '{}'", what, token)))?;
+        let idx = match raw {
+            0 => None,
+            r if r > 0 => Some(r as usize - 1),
+            r => (count as i64 + r).try_into().ok(),
+        };
+        match idx {
+            Some(i) if i < count => Ok(i),
+            _ => Err(self.error(format!("{} index {} out of range ({} defined)", what, raw, count))),
+        }
+    }
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3 {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+    }
+}
+
+fn read(path: &Path) -> Result<String, MeshError> {
+    fs::read_to_string(path).map_err(|err| MeshError::Io(path.to_path_buf(), err))
+}
+
+struct MtlDesc {
+    kd: Option<Colour>, //Unset lets map_Kd show unscaled
+    map_kd: Option<ImageTexture>,
+    ks: Colour,
+    ns: f64,
+    ni: f64,
+    dissolve: f64,
+    illum: i32,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        MtlDesc {
+            kd: None,
+            map_kd: None,
+            ks: Colour::default(),
+            ns: 0.0,
+            ni: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlDesc {
+    fn into_material(self) -> Material {
+        //Transparent illumination models or partial dissolve mean glass,
+        //a mirror model or a specular-only colour means metal, anything else is diffuse
+        let max = |c: &Colour| c.x.max(c.y).max(c.z);
+        let grey = Colour {
+            x: 0.5,
+            y: 0.5,
+            z: 0.5,
+        };
+        let kd = self.kd.unwrap_or(grey);
+        if matches!(self.illum, 4 | 6 | 7 | 9) || self.dissolve < 1.0 {
+            Material::Dielectric { idx_refract: self.ni }
+        } else if max(&self.ks) > 0.0 && (self.illum == 3 || (max(&kd) < 0.01 && self.map_kd.is_none())) {
+            // Phong exponent to a blur radius, high exponents give sharp reflections
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt().clamp(0.0, 1.0);
+            Material::Metal {
+                albedo: Texture::Solid(self.ks),
+                fuzz,
+            }
+        } else {
+            // The diffuse map is scaled by Kd when both are given
+            let albedo = match (self.map_kd, self.kd) {
+                (Some(map), Some(kd)) => Texture::Image(Arc::new(map.tinted(&kd))),
+                (Some(map), None) => Texture::Image(Arc::new(map)),
+                (None, _) => Texture::Solid(kd),
+            };
+            Material::Lambertian { albedo }
+        }
+    }
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, MeshError> {
+    let src = read(path)?;
+    let mut parser = Parser { path, line: 0 };
+    let mut descs: Vec<(String, MtlDesc)> = Vec::new();
+    for (n, raw) in src.lines().enumerate() {
+        parser.line = n + 1;
+        let line = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        if keyword == "newmtl" {
+            descs.push((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+        let current = match descs.last_mut() {
+            Some((_, desc)) => desc,
+            None => return Err(parser.error(format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => current.kd = Some(vec3(parser.floats::<3>(&args, keyword)?)),
+            "map_Kd" => {
+                // Options such as -s or -o come first, the file name last
+                let name = args
+                    .last()
+                    .ok_or_else(|| parser.error("'map_Kd' needs a file name".to_string()))?;
+                let dir = path.parent().unwrap_or(Path::new(""));
+                let image = ImageTexture::load(&dir.join(name), WrapMode::Repeat)
+                    .map_err(|err| parser.error(err.to_string()))?;
+                current.map_kd = Some(image);
+            }
+            "Ks" => current.ks = vec3(parser.floats::<3>(&args, keyword)?),
+            "Ns" => current.ns = parser.floats::<1>(&args, keyword)?[0],
+            "Ni" => current.ni = parser.floats::<1>(&args, keyword)?[0],
+            "d" => current.dissolve = parser.floats::<1>(&args, keyword)?[0],
+            "Tr" => current.dissolve = 1.0 - parser.floats::<1>(&args, keyword)?[0],
+            "illum" => current.illum = parser.floats::<1>(&args, keyword)?[0] as i32,
+            _ => {} // Ka, Ke, other texture maps and friends have no counterpart yet
+        }
+    }
+    Ok(descs
+        .into_iter()
+        .map(|(name, desc)| (name, desc.into_material()))
+        .collect())
+}
+
+pub fn load_obj(path: &Path, default_material: Material) -> Result<MeshData, MeshError> {
+    let src = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut parser = Parser { path, line: 0 };
+
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut library: HashMap<String, Material> = HashMap::new();
+
+    // OBJ indexes positions, uvs and normals separately; each distinct
+    // combination used by a face becomes one shared mesh vertex.
+    let mut corners: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut corner_list: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut mesh = MeshData {
+        materials: vec![default_material],
+        ..Default::default()
+    };
+    let mut material_slots: HashMap<String, u32> = HashMap::new();
+    let mut current_material = 0u32;
+
+    for (n, raw) in src.lines().enumerate() {
+        parser.line = n + 1;
+        let line = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => positions.push(vec3(parser.floats::<3>(&args, keyword)?)),
+            "vn" => normals.push(vec3(parser.floats::<3>(&args, keyword)?).unit()),
+            "vt" => {
+                // The w coordinate and a missing v are both allowed
+                let u = parser.floats::<1>(&args, keyword)?[0];
+                let v = match args.len() > 1 {
+                    true => parser.floats::<1>(&args[1..], keyword)?[0],
+                    false => 0.0,
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parser.error(format!("face needs at least 3 vertices, found {}", args.len())));
+                }
+                let mut face = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let p = parser.index(parts.next().unwrap_or(""), positions.len(), "position")?;
+                    let t = match parts.next() {
+                        Some(s) if !s.is_empty() => Some(parser.index(s, uvs.len(), "texture")?),
+                        _ => None,
+                    };
+                    let nrm = match parts.next() {
+                        Some(s) if !s.is_empty() => Some(parser.index(s, normals.len(), "normal")?),
+                        _ => None,
+                    };
+                    let key = (p, t, nrm);
+                    let id = *corners.entry(key).or_insert_with(|| {
+                        corner_list.push(key);
+                        (corner_list.len() - 1) as u32
+                    });
+                    face.push(id);
+                }
+                for k in 1..face.len() - 1 {
+                    mesh.indices.push([face[0], face[k], face[k + 1]]);
+                    mesh.face_materials.push(current_material);
+                }
+            }
+            "mtllib" => {
+                for name in &args {
+                    library.extend(load_mtl(&dir.join(name))?);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current_material = match material_slots.get(&name) {
+                    Some(slot) => *slot,
+                    None => {
+                        let mat = library
+                            .get(&name)
+                            .ok_or_else(|| parser.error(format!("material '{}' is not in any mtllib", name)))?;
+                        mesh.materials.push(mat.clone());
+                        let slot = (mesh.materials.len() - 1) as u32;
+                        material_slots.insert(name, slot);
+                        slot
+                    }
+                };
+            }
+            _ => {} // o, g, s, l and other statements
+        }
+    }
+
+    // Attributes are only kept when every vertex has them, partial data cannot be interpolated
+    let all_uvs = corner_list.iter().all(|(_, t, _)| t.is_some());
+    let all_normals = corner_list.iter().all(|(_, _, nrm)| nrm.is_some());
+    for (p, t, nrm) in corner_list {
+        mesh.positions.push(positions[p]);
+        if all_uvs {
+            mesh.uvs.push(t.map_or((0.0, 0.0), |t| uvs[t]));
+        }
+        if all_normals {
+            mesh.normals.push(nrm.map_or(Vec3::default(), |nrm| normals[nrm]));
+        }
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{default_mesh_material, load_temp};
+
+    fn load(name: &str, contents: &str) -> Result<MeshData, MeshError> {
+        load_temp(name, contents.as_bytes(), |path| load_obj(path, default_mesh_material(0.5)))
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let mesh = load(
+            "fan.obj",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0.5 2 0\nv 0 1 0\nf 1 2 3 4 5\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert_eq!(mesh.face_materials, [0, 0, 0]);
+        assert_eq!(mesh.positions.len(), 5);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_latest_vertex() {
+        let src = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+f -3/-3 -2/-2 -1/-1
+v 0 0 1
+f -4 -3 -1
+";
+        let mesh = load("negative.obj", src).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+        let position = |face: usize, corner: usize| {
+            let p = mesh.positions[mesh.indices[face][corner] as usize];
+            (p.x, p.y, p.z)
+        };
+        assert_eq!(position(0, 0), (0.0, 0.0, 0.0));
+        assert_eq!(position(0, 2), (0.0, 1.0, 0.0));
+        assert_eq!(position(1, 1), (1.0, 0.0, 0.0));
+        assert_eq!(position(1, 2), (0.0, 0.0, 1.0));
+        // The second face has no uvs, so the mesh keeps none
+        assert!(mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn shared_corners_become_one_vertex() {
+        let mesh = load("shared.obj", "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n").unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn bad_indices_report_their_line() {
+        for (name, src) in [
+            ("zero.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"),
+            ("past_end.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"),
+            ("before_start.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 1 2\n"),
+        ] {
+            match load(name, src) {
+                Err(MeshError::Parse { line, message, .. }) => {
+                    assert_eq!(line, 4);
+                    assert!(message.contains("out of range"), "{}", message);
+                }
+                _ => panic!("{} should not load", name),
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::colour::{to_display, to_linear, to_u16, to_u8, write_colour};
+use crate::exr::{write_exr, ExrCompression};
+use crate::framebuffer::Framebuffer;
+use crate::hdr::{write_hdr, write_pfm};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io(io::Error),
+    Png(png::EncodingError),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::Io(err) => write!(f, "failed to write image: {}", err),
+            OutputError::Png(err) => write!(f, "failed to encode PNG: {}", err),
+            OutputError::UnsupportedFormat(name) => write!(f, "unsupported image format '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutputError::Io(err) => Some(err),
+            OutputError::Png(err) => Some(err),
+            OutputError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for OutputError {
+    fn from(err: io::Error) -> Self {
+        OutputError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for OutputError {
+    fn from(err: png::EncodingError) -> Self {
+        OutputError::Png(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png8,
+    Png16,
+    Ppm,      //Binary P6
+    PpmAscii, //Plain text P3
+    Hdr,      //Radiance RGBE, linear
+    Pfm,      //Portable Float Map, linear
+    Exr,      //OpenEXR, ZIP compressed 32-bit float, linear
+    ExrUncompressed,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Result<Self, OutputError> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" | "png8" => Ok(ImageFormat::Png8),
+            "png16" => Ok(ImageFormat::Png16),
+            "ppm" | "p6" => Ok(ImageFormat::Ppm),
+            "ppm-ascii" | "p3" => Ok(ImageFormat::PpmAscii),
+            "hdr" | "rgbe" => Ok(ImageFormat::Hdr),
+            "pfm" => Ok(ImageFormat::Pfm),
+            "exr" | "exr-zip" => Ok(ImageFormat::Exr),
+            "exr-uncompressed" => Ok(ImageFormat::ExrUncompressed),
+            _ => Err(OutputError::UnsupportedFormat(name.to_string())),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, OutputError> {
+        //"-" is stdout, kept as ASCII PPM so `> image.ppm` redirects still work
+        if path.as_os_str() == "-" {
+            return Ok(ImageFormat::PpmAscii);
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => Self::from_name(ext),
+            None => Err(OutputError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+pub fn write_image(path: &Path, format: ImageFormat, image: &Framebuffer) -> Result<(), OutputError> {
+    if path.as_os_str() == "-" {
+        let stdout = io::stdout();
+        return encode(stdout.lock(), format, image);
+    }
+    let file = BufWriter::new(File::create(path)?);
+    encode(file, format, image)
+}
+
+pub fn encode<W: Write>(mut out: W, format: ImageFormat, image: &Framebuffer) -> Result<(), OutputError> {
+    let (width, height, pixels) = (image.width, image.height, image.pixels());
+    match format {
+        ImageFormat::Png8 | ImageFormat::Png16 => {
+            let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            let data: Vec<u8> = match format {
+                ImageFormat::Png16 => {
+                    encoder.set_depth(png::BitDepth::Sixteen);
+                    pixels
+                        .iter()
+                        .flat_map(|&pixel_colour| {
+                            let rgb = to_display(pixel_colour);
+                            [to_u16(rgb.0), to_u16(rgb.1), to_u16(rgb.2)]
+                        })
+                        .flat_map(|c| c.to_be_bytes()) //PNG samples are big-endian
+                        .collect()
+                }
+                _ => {
+                    encoder.set_depth(png::BitDepth::Eight);
+                    pixels
+                        .iter()
+                        .flat_map(|&pixel_colour| {
+                            let rgb = to_display(pixel_colour);
+                            [to_u8(rgb.0), to_u8(rgb.1), to_u8(rgb.2)]
+                        })
+                        .collect()
+                }
+            };
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&data)?;
+            writer.finish()?;
+        }
+        ImageFormat::Ppm => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            let data: Vec<u8> = pixels
+                .iter()
+                .flat_map(|&pixel_colour| {
+                    let rgb = to_display(pixel_colour);
+                    [to_u8(rgb.0), to_u8(rgb.1), to_u8(rgb.2)]
+                })
+                .collect();
+            out.write_all(&data)?;
+            out.flush()?;
+        }
+        ImageFormat::PpmAscii => {
+            writeln!(out, "P3\n{} {}\n255", width, height)?;
+            for &pixel_colour in pixels {
+                write_colour(&mut out, pixel_colour)?;
+            }
+            out.flush()?;
+        }
+        ImageFormat::Hdr | ImageFormat::Pfm | ImageFormat::Exr | ImageFormat::ExrUncompressed => {
+            let linear: Vec<[f32; 3]> = pixels
+                .iter()
+                .map(|&pixel_colour| to_linear(pixel_colour))
+                .collect();
+            match format {
+                ImageFormat::Hdr => write_hdr(out, width, height, &linear)?,
+                ImageFormat::Pfm => write_pfm(out, width, height, &linear)?,
+                ImageFormat::Exr => write_exr(out, width, height, &linear, ExrCompression::Zip)?,
+                _ => write_exr(out, width, height, &linear, ExrCompression::None)?,
+            }
+        }
+    }
+    Ok(())
+}
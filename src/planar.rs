@@ -0,0 +1,346 @@
+use crate::{
+    aabb::{Aabb, UNIVERSE_AABB},
+    hittable::{HitRecord, HitRecordProperties, Hittable},
+    interval::Interval,
+    light::{Light, LightShape},
+    material::Material,
+    math::Onb,
+    ray::{Ray, RayProperties},
+    validate::{fmt_vec3, Report},
+    vec3::{Vec3, VectorProperties},
+};
+use std::f64::consts::PI;
+
+//Flat primitives. Each one intersects its supporting plane first and then
+//decides whether the hit point lies inside its own boundary.
+
+pub struct Quad {
+    pub(crate) q: Vec3, //Corner
+    pub(crate) u: Vec3, //Edge vectors from the corner
+    pub(crate) v: Vec3,
+    pub(crate) mat_type: Material,
+    normal: Vec3,
+    w: Vec3, //Cached n / (n . n), maps a planar offset onto (alpha, beta)
+    area: f64,
+}
+
+pub struct Plane {
+    pub(crate) point: Vec3,
+    pub(crate) normal: Vec3,
+    pub(crate) mat_type: Material,
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+pub struct Disk {
+    pub(crate) centre: Vec3,
+    pub(crate) normal: Vec3,
+    pub(crate) radius: f64,
+    pub(crate) mat_type: Material,
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+pub struct Triangle {
+    pub(crate) a: Vec3,
+    pub(crate) b: Vec3,
+    pub(crate) c: Vec3,
+    pub(crate) mat_type: Material,
+}
+
+fn hit_plane(point: &Vec3, normal: &Vec3, r: &Ray, ray_t: Interval) -> Option<f64> {
+    // No hit if the ray is parallel to the plane.
+    let denom = *normal * r.direction();
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+    let t = (*normal * (*point - r.origin())) / denom;
+    match ray_t.surrounds(t) {
+        true => Some(t),
+        false => None,
+    }
+}
+
+fn record(
+    r: &Ray,
+    t: f64,
+    outward_normal: &Vec3,
+    (u, v): (f64, f64),
+    area_pdf: f64,
+    mat_type: &Material,
+    rec: &mut Option<HitRecord>,
+) {
+    *rec = Some(HitRecord {
+        p: r.at(t),
+        mat_type: mat_type.clone(),
+        t,
+        u,
+        v,
+        area_pdf,
+        ..Default::default()
+    });
+    HitRecord::set_face_normal(r, outward_normal, rec);
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat_type: Material) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit();
+        Quad {
+            q,
+            u,
+            v,
+            mat_type,
+            normal,
+            w: n / (n * n),
+            area: n.d_euclid(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        let t = match hit_plane(&self.q, &self.normal, r, ray_t) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        // Determine the hit point lies within the planar shape using its plane coordinates.
+        let planar_hitpt = r.at(t) - self.q;
+        let alpha = self.w * planar_hitpt.cross(&self.v);
+        let beta = self.w * self.u.cross(&planar_hitpt);
+        let unit_interval = Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        let area_pdf = self.mat_type.light_area_pdf(self.area);
+        record(r, t, &self.normal, (alpha, beta), area_pdf, &self.mat_type, rec);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // Box around all four corners
+        let diagonal1 = Aabb::from_points(self.q, self.q + self.u + self.v);
+        let diagonal2 = Aabb::from_points(self.q + self.u, self.q + self.v);
+        Aabb::surrounding(&diagonal1, &diagonal2)
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!("quad at {}", fmt_vec3(&self.q));
+        if self.u.cross(&self.v).near_zero() {
+            report.error(format!("{}: edges {} and {} are parallel", context, fmt_vec3(&self.u), fmt_vec3(&self.v)));
+        }
+        report.check_material(&self.mat_type, &context);
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        match &self.mat_type {
+            Material::DiffuseLight { emit } => vec![Light::new(
+                LightShape::Quad {
+                    q: self.q,
+                    u: self.u,
+                    v: self.v,
+                },
+                emit.clone(),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, mat_type: Material) -> Self {
+        let Onb {
+            u: tangent,
+            v: bitangent,
+            w: normal,
+        } = Onb::from_w(&normal);
+        Plane {
+            point,
+            normal,
+            mat_type,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        let t = match hit_plane(&self.point, &self.normal, r, ray_t) {
+            Some(t) => t,
+            None => return false,
+        };
+        // UVs are world-unit distances along the tangent frame, textures tile them.
+        // An infinite plane cannot be sampled as a light.
+        let offset = r.at(t) - self.point;
+        let uv = (offset * self.tangent, offset * self.bitangent);
+        record(r, t, &self.normal, uv, 0.0, &self.mat_type, rec);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        UNIVERSE_AABB
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!("plane through {}", fmt_vec3(&self.point));
+        if !self.normal.d_euclidsq().is_finite() || self.normal.near_zero() {
+            report.error(format!("{}: normal must be a non-zero vector", context));
+        }
+        report.check_material(&self.mat_type, &context);
+    }
+}
+
+impl Disk {
+    pub fn new(centre: Vec3, normal: Vec3, radius: f64, mat_type: Material) -> Self {
+        let Onb {
+            u: tangent,
+            v: bitangent,
+            w: normal,
+        } = Onb::from_w(&normal);
+        Disk {
+            centre,
+            normal,
+            radius,
+            mat_type,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        let t = match hit_plane(&self.centre, &self.normal, r, ray_t) {
+            Some(t) => t,
+            None => return false,
+        };
+        let offset = r.at(t) - self.centre;
+        let dist_sq = offset.d_euclidsq();
+        if dist_sq > self.radius * self.radius {
+            return false;
+        }
+        // Polar UVs: u is the angle around the centre, v the fraction of the radius
+        let phi = (offset * self.bitangent).atan2(offset * self.tangent) + PI;
+        let u = phi / (2.0 * PI);
+        let v = dist_sq.sqrt() / self.radius;
+        let area_pdf = self.mat_type.light_area_pdf(PI * self.radius * self.radius);
+        record(r, t, &self.normal, (u, v), area_pdf, &self.mat_type, rec);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // A tilted disk extends r * sqrt(1 - n_i^2) along each axis
+        let extent = |n: f64| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let half = Vec3 {
+            x: extent(self.normal.x),
+            y: extent(self.normal.y),
+            z: extent(self.normal.z),
+        };
+        Aabb::from_points(self.centre - half, self.centre + half)
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!("disk at {} radius {}", fmt_vec3(&self.centre), self.radius);
+        if self.radius <= 0.0 || !self.radius.is_finite() {
+            report.error(format!("{}: radius must be positive", context));
+        }
+        if !self.normal.d_euclidsq().is_finite() || self.normal.near_zero() {
+            report.error(format!("{}: normal must be a non-zero vector", context));
+        }
+        report.check_material(&self.mat_type, &context);
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        match &self.mat_type {
+            Material::DiffuseLight { emit } => vec![Light::new(
+                LightShape::Disk {
+                    centre: self.centre,
+                    normal: self.normal,
+                    radius: self.radius,
+                    tangent: self.tangent,
+                    bitangent: self.bitangent,
+                },
+                emit.clone(),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Möller-Trumbore ray/triangle test. Returns (t, b1, b2) where b1 and b2 are the
+/// barycentric weights of the second and third vertices.
+pub fn hit_triangle(a: &Vec3, b: &Vec3, c: &Vec3, r: &Ray, ray_t: Interval) -> Option<(f64, f64, f64)> {
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+    let pvec = r.direction().cross(&edge2);
+    let det = edge1 * pvec;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - *a;
+    let b1 = (tvec * pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(&edge1);
+    let b2 = (r.direction() * qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = (edge2 * qvec) * inv_det;
+    match ray_t.surrounds(t) {
+        true => Some((t, b1, b2)),
+        false => None,
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        match hit_triangle(&self.a, &self.b, &self.c, r, ray_t) {
+            Some((t, b1, b2)) => {
+                // Counter-clockwise winding seen from the front
+                let n = (self.b - self.a).cross(&(self.c - self.a));
+                let area_pdf = self.mat_type.light_area_pdf(n.d_euclid() / 2.0);
+                record(r, t, &n.unit(), (b1, b2), area_pdf, &self.mat_type, rec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::surrounding(&Aabb::from_points(self.a, self.b), &Aabb::from_points(self.c, self.c))
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let context = format!(
+            "triangle {} {} {}",
+            fmt_vec3(&self.a),
+            fmt_vec3(&self.b),
+            fmt_vec3(&self.c)
+        );
+        if (self.b - self.a).cross(&(self.c - self.a)).near_zero() {
+            report.error(format!("{}: vertices are collinear, the triangle has no area", context));
+        }
+        report.check_material(&self.mat_type, &context);
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        match &self.mat_type {
+            Material::DiffuseLight { emit } => vec![Light::new(
+                LightShape::Triangle {
+                    a: self.a,
+                    b: self.b,
+                    c: self.c,
+                    uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+                },
+                emit.clone(),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    colour::{gamma_to_linear, Colour},
+    material::Material,
+    mesh::{MeshData, MeshError},
+    vec3::{Vec3, VectorProperties},
+};
+use std::fs;
+use std::path::Path;
+
+//Stanford PLY import in ascii, binary_little_endian and binary_big_endian flavours.
+//Reads positions, optional normals, texture coordinates and colours from the
+//vertex element and polygon lists from the face element; other elements are skipped.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLe,
+    BinaryBe,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    kind: Scalar,
+    list_count: Option<Scalar>, //Type of the length prefix for list properties
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+//Streams scalar values from the body of the file, whichever encoding it uses
+struct Body<'a> {
+    path: &'a Path,
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl Body<'_> {
+    fn error(&self, message: String) -> MeshError {
+        //Binary bodies have no lines, the byte offset is more useful there
+        MeshError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: match self.format {
+                Format::Ascii => message,
+                _ => format!("{} (binary data at byte {})", message, self.pos),
+            },
+        }
+    }
+
+    fn next_token(&mut self) -> Option<&str> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            if self.data[self.pos] == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        match start < self.pos {
+            true => std::str::from_utf8(&self.data[start..self.pos]).ok(),
+            false => None,
+        }
+    }
+
+    fn scalar(&mut self, kind: Scalar) -> Result<f64, MeshError> {
+        if self.format == Format::Ascii {
+            let token = match self.next_token() {
+                Some(token) => token.to_string(),
+                None => return Err(self.error("unexpected end of file".to_string())),
+            };
+            return token
+                .parse::<f64>()
+                .map_err(|_| self.error(format!("'{}' is not a number", token)));
+        }
+
+        let size = kind.size();
+        if self.pos + size > self.data.len() {
+            return Err(self.error("unexpected end of file".to_string()));
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.pos..self.pos + size]);
+        if self.format == Format::BinaryBe {
+            bytes[..size].reverse();
+        }
+        self.pos += size;
+        let b2 = [bytes[0], bytes[1]];
+        let b4 = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(match kind {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes(b2) as f64,
+            Scalar::U16 => u16::from_le_bytes(b2) as f64,
+            Scalar::I32 => i32::from_le_bytes(b4) as f64,
+            Scalar::U32 => u32::from_le_bytes(b4) as f64,
+            Scalar::F32 => f32::from_le_bytes(b4) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes),
+        })
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    body_start: usize,
+    lines: usize,
+}
+
+fn parse_header(path: &Path, data: &[u8]) -> Result<Header, MeshError> {
+    let error = |line: usize, message: String| MeshError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut pos = 0;
+    let mut line_no = 0;
+    loop {
+        let end = match data[pos..].iter().position(|&b| b == b'\n') {
+            Some(offset) => pos + offset,
+            None => return Err(error(line_no + 1, "header has no end_header line".to_string())),
+        };
+        line_no += 1;
+        let line = String::from_utf8_lossy(&data[pos..end]).trim().to_string();
+        pos = end + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if line_no == 1 => {}
+            _ if line_no == 1 => return Err(error(1, "not a PLY file, missing 'ply' magic".to_string())),
+            ["format", kind, _version] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLe,
+                    "binary_big_endian" => Format::BinaryBe,
+                    other => return Err(error(line_no, format!("unknown format '{}'", other))),
+                })
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| error(line_no, format!("bad element count '{}'", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_kind, kind, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind).ok_or_else(|| error(line_no, format!("unknown type '{}'", kind)))?,
+                    list_count: Some(
+                        Scalar::parse(count_kind)
+                            .ok_or_else(|| error(line_no, format!("unknown type '{}'", count_kind)))?,
+                    ),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| error(line_no, "property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            ["property", kind, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind).ok_or_else(|| error(line_no, format!("unknown type '{}'", kind)))?,
+                    list_count: None,
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| error(line_no, "property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            ["end_header"] => break,
+            _ => return Err(error(line_no, format!("unexpected header line '{}'", line))),
+        }
+    }
+    Ok(Header {
+        format: format.ok_or_else(|| error(line_no, "header has no format line".to_string()))?,
+        elements,
+        body_start: pos,
+        lines: line_no,
+    })
+}
+
+pub fn load_ply(path: &Path, material: Material) -> Result<MeshData, MeshError> {
+    let data = fs::read(path).map_err(|err| MeshError::Io(path.to_path_buf(), err))?;
+    let header = parse_header(path, &data)?;
+    let mut body = Body {
+        path,
+        format: header.format,
+        data: &data[header.body_start..],
+        pos: 0,
+        line: header.lines + 1,
+    };
+
+    let mut mesh = MeshData {
+        materials: vec![material],
+        ..Default::default()
+    };
+    for element in &header.elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| p.list_count.is_none() && names.contains(&p.name.as_str()))
+        };
+        let (x, y, z) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let normal = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let uv = (
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        );
+        let rgb = (
+            find(&["red", "diffuse_red", "r"]),
+            find(&["green", "diffuse_green", "g"]),
+            find(&["blue", "diffuse_blue", "b"]),
+        );
+        let indices = element
+            .properties
+            .iter()
+            .position(|p| p.list_count.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index"));
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            let mut polygon: Vec<u32> = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.list_count {
+                    None => values[i] = body.scalar(property.kind)?,
+                    Some(count_kind) => {
+                        let count = body.scalar(count_kind)? as usize;
+                        for _ in 0..count {
+                            let value = body.scalar(property.kind)?;
+                            if Some(i) == indices {
+                                if value < 0.0 || value as usize >= mesh.positions.len() {
+                                    return Err(body.error(format!(
+                                        "vertex index {} out of range ({} vertices)",
+                                        value,
+                                        mesh.positions.len()
+                                    )));
+                                }
+                                polygon.push(value as u32);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                let (x, y, z) = match (x, y, z) {
+                    (Some(x), Some(y), Some(z)) => (x, y, z),
+                    _ => return Err(body.error("vertex element needs x, y and z".to_string())),
+                };
+                mesh.positions.push(Vec3 {
+                    x: values[x],
+                    y: values[y],
+                    z: values[z],
+                });
+                if let (Some(nx), Some(ny), Some(nz)) = normal {
+                    let n = Vec3 {
+                        x: values[nx],
+                        y: values[ny],
+                        z: values[nz],
+                    };
+                    mesh.normals.push(n.unit());
+                }
+                if let (Some(u), Some(v)) = uv {
+                    mesh.uvs.push((values[u], values[v]));
+                }
+                if let (Some(r), Some(g), Some(b)) = rgb {
+                    // Integer colours are 0-255 display values, float ones already linear 0-1
+                    let channel = |idx: usize| match element.properties[idx].kind {
+                        Scalar::F32 | Scalar::F64 => values[idx],
+                        _ => gamma_to_linear(values[idx] / 255.0),
+                    };
+                    mesh.colours.push(Colour {
+                        x: channel(r),
+                        y: channel(g),
+                        z: channel(b),
+                    });
+                }
+            } else if element.name == "face" && polygon.len() >= 3 {
+                for k in 1..polygon.len() - 1 {
+                    mesh.indices.push([polygon[0], polygon[k], polygon[k + 1]]);
+                    mesh.face_materials.push(0);
+                }
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{default_mesh_material, load_temp};
+
+    fn load(name: &str, contents: &[u8]) -> Result<MeshData, MeshError> {
+        load_temp(name, contents, |path| load_ply(path, default_mesh_material(0.5)))
+    }
+
+    fn xyz(p: Vec3) -> (f64, f64, f64) {
+        (p.x, p.y, p.z)
+    }
+
+    #[test]
+    fn ascii_quad_with_colours() {
+        let src = "\
+ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 255 0 0
+1 1 0 0 0 255
+0 1 0 0 0 255
+4 0 1 2 3
+";
+        let mesh = load("ascii.ply", src.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(xyz(mesh.positions[2]), (1.0, 1.0, 0.0));
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.face_materials, [0, 0]);
+        // 255 is display white, which is linear 1
+        assert_eq!(xyz(mesh.colours[0]), (1.0, 0.0, 0.0));
+        assert_eq!(xyz(mesh.colours[3]), (0.0, 0.0, 1.0));
+        assert!(mesh.normals.is_empty() && mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn binary_little_endian_triangle() {
+        let mut data = b"\
+ply
+format binary_little_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for position in [[0.0f32, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]] {
+            for value in position.iter().chain(&[0.0, 0.0, 3.0]) {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        data.push(3);
+        for index in [0u32, 1, 2] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let mesh = load("binary_le.ply", &data).unwrap();
+        assert_eq!(xyz(mesh.positions[1]), (2.0, 0.0, 0.0));
+        assert_eq!(xyz(mesh.positions[2]), (0.0, 2.0, 0.0));
+        assert_eq!(mesh.indices, [[0, 1, 2]]);
+        // Normals are stored unit length
+        assert_eq!(xyz(mesh.normals[0]), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn truncated_and_malformed_files_are_errors() {
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n";
+        let mut data = header.as_bytes().to_vec();
+        data.extend_from_slice(&1f32.to_le_bytes());
+        assert!(load("truncated.ply", &data).is_err());
+        assert!(load("no_magic.ply", b"off\nformat ascii 1.0\nend_header\n").is_err());
+        assert!(load("bad_index.ply", b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n3 0 0 1\n").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use crate::colour::Colour;
+use crate::framebuffer::Framebuffer;
+
+//Image-space operations applied to a Framebuffer between render and encode.
+//All of them work on linear radiance, display gamma is left to the encoder.
+#[derive(Debug, Clone, Copy)]
+pub enum PostProcess {
+    Exposure(f64), //Scale by 2^stops
+    Clamp(f64),    //Cap each channel, useful for taming fireflies
+    Reinhard,
+    Aces, //Narkowicz's fit of the ACES filmic curve
+}
+
+impl PostProcess {
+    pub fn apply(&self, image: &mut Framebuffer) {
+        for pixel in image.pixels_mut() {
+            *pixel = self.apply_pixel(*pixel);
+        }
+    }
+
+    fn apply_pixel(&self, c: Colour) -> Colour {
+        match self {
+            PostProcess::Exposure(stops) => 2f64.powf(*stops) * c,
+            PostProcess::Clamp(max) => Colour {
+                x: c.x.min(*max),
+                y: c.y.min(*max),
+                z: c.z.min(*max),
+            },
+            PostProcess::Reinhard => Colour {
+                x: c.x / (1.0 + c.x),
+                y: c.y / (1.0 + c.y),
+                z: c.z / (1.0 + c.z),
+            },
+            PostProcess::Aces => {
+                let aces = |x: f64| {
+                    let (a, b, cc, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                    ((x * (a * x + b)) / (x * (cc * x + d) + e)).clamp(0.0, 1.0)
+                };
+                Colour {
+                    x: aces(c.x),
+                    y: aces(c.y),
+                    z: aces(c.z),
+                }
+            }
+        }
+    }
+}
@@ -1,17 +1,16 @@
-#[path = "vec3.rs"]
-mod vec3;
-
 use crate::vec3::Vec3;
 #[derive(Default)]
 pub struct Ray {
     pub(crate) origin: Vec3,
     pub(crate) direction: Vec3,
+    pub(crate) time: f64, //Moment within the shutter interval the ray was cast
 }
 
 pub trait RayProperties {
     fn origin(&self) -> Vec3;
     fn direction(&self) -> Vec3;
     fn at(&self, t: f64) -> Vec3;
+    fn time(&self) -> f64;
 }
 
 impl RayProperties for Ray {
@@ -26,4 +25,8 @@ impl RayProperties for Ray {
     fn at(&self, t: f64) -> Vec3 {
         self.origin + t * self.direction
     }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
 }
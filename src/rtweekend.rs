@@ -1,4 +1,5 @@
 use rand::prelude::*;
+use std::cell::RefCell;
 //const PI: f64 = std::f64::consts::PI;
 //const INFINITY: f64 = f64::INFINITY;
 
@@ -6,15 +7,18 @@ use rand::prelude::*;
 //pub fn degrees_to_radians(deg: f64) -> f64 {
 //    deg * PI / 180.0
 //}
+thread_local! {
+    //Per-thread generator so a fixed seed can make renders reproducible
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
 #[inline]
 pub fn rand0_1() -> f64{
-    let mut rng = rand::thread_rng();
-    let y: f64 = rng.gen(); // generates a float between 0 and 1
-    y
+    RNG.with(|rng| rng.borrow_mut().gen()) // generates a float between 0 and 1
 }
 #[inline]
 pub fn rand_range(min:f64,max:f64) -> f64{
-    let mut rng = rand::thread_rng();
-    let y: f64 = rng.gen_range(min..max); // generates a float between 0 and 1
-    y
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
 }
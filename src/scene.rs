@@ -0,0 +1,889 @@
+use crate::background::{Background, EnvironmentLight, Sky};
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::gltf_scene::load_gltf;
+use crate::hittable::{Hittable, HittableList, MovingSphere, Sphere};
+use crate::integrator::{IntegratorKind, MisHeuristic};
+use crate::light::Light;
+use crate::material::Material;
+use crate::mesh::{load_mesh, TriangleMesh};
+use crate::planar::{Disk, Plane, Quad, Triangle};
+use crate::texture::{ImageTexture, NoisePattern, NoiseTexture, Texture, WrapMode};
+use crate::math::Mat4;
+use crate::transform::Transform;
+use crate::vec3::Vec3;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+//TOML scene description. A scene has optional [camera] and [background] tables, named
+//[textures.<name>] and [materials.<name>] tables, an array of [[objects]] referring to
+//materials by name and an optional array of [[lights]]:
+//
+//  [camera]
+//  lookfrom = [0.0, 3.0, 3.0]
+//
+//  [textures.wood]
+//  type = "image"
+//  path = "wood.png"
+//
+//  [materials.floor]
+//  type = "lambertian"
+//  albedo = "wood"
+//
+//  [materials.ground]
+//  type = "lambertian"
+//  albedo = [0.8, 0.8, 0.0]
+//
+//  [[objects]]
+//  type = "sphere"
+//  centre = [0.0, -100.5, -1.0]
+//  radius = 100.0
+//  material = "ground"
+//
+//Material types are default, lambertian and metal (albedo, metal also fuzz),
+//dielectric (ior) and diffuse_light (emit, a colour or texture that may exceed 1).
+//Diffuse surfaces aim shadow rays at diffuse_light spheres, quads, disks, triangles and
+//meshes; lit planes and moving spheres are only found by rays bouncing into them. Light
+//found both ways is shared out by multiple importance sampling, with the camera's
+//mis = "power" (default) or "balance" heuristic. The camera's integrator = "path"
+//(default) renders the scene, "normals" shows the surface normals instead.
+//
+//The background is what rays leaving the scene see, and lights the scene with it:
+//  solid          colour, e.g. black for an indoor scene lit only by diffuse_light
+//  gradient       bottom and top colours, blended by direction height (the default
+//                 is white to sky blue)
+//  environment    texture (a name) or path (to an image, usually an HDR panorama),
+//                 mapped around the scene like a sphere's uvs. An image given by path
+//                 is also a light that diffuse surfaces sample directly, which cuts the
+//                 noise from small bright areas like the sun; it takes optional
+//                 rotation = 0 (degrees about +Y) and intensity = 1
+//  sky            sun (direction towards it), optional turbidity = 3 (2 is clear, 10
+//                 hazy) and intensity = 0.05; the Preetham daylight model, without the
+//                 sun itself
+//
+//A material's albedo is either a colour or the name of a texture. Texture types:
+//  solid          colour
+//  image          path to a PNG, PPM or Radiance HDR file, optional wrap = "repeat"
+//                 (default), "clamp" or "mirror" for coordinates outside [0, 1]
+//  checker        size (cell edge in world units), even and odd (colours or textures)
+//  uv_checker     columns and rows across the surface's uv square, even and odd
+//  perlin, turbulence, marble, wood, worley
+//                 noise patterns blending from low (default black) to high (default
+//                 white), optional scale = 1 (frequency) and seed = 0
+//
+//Object types and their fields:
+//  sphere         centre, radius
+//  moving_sphere  centre0, centre1, radius, optional time0 = 0 and time1 = 1
+//  quad           q (corner), u and v (edge vectors)
+//  plane          point, normal (infinite)
+//  disk           centre, normal, radius
+//  triangle       a, b, c (counter-clockwise seen from the front)
+//  mesh           path to an OBJ, PLY or STL file, optional material for faces the
+//                 file does not assign one to (PLY vertex colours tint Lambertian ones)
+//  gltf           path to a .gltf or .glb file whose meshes are added, its camera is ignored
+//
+//Any object can also have translate = [x, y, z], rotate = [x, y, z] (degrees about each
+//axis, applied x first) and scale = s or [x, y, z]. Objects naming the same mesh or
+//glTF file share one copy of it, so placing a model many times is cheap.
+//
+//Lights without geometry, which only shadow rays from diffuse surfaces see:
+//  point          position, intensity (a colour, may exceed 1), optional falloff = 2
+//                 (the power of the distance the light fades with)
+//  spot           as point plus direction (where it shines), inner_angle and
+//                 outer_angle (degrees from the axis, fading smoothly in between)
+//  directional    direction (towards the light), irradiance (received by a surface
+//                 facing it), optional angular_diameter = 0 (degrees, about 0.53 for the
+//                 sun, softens shadows)
+//
+//A .gltf or .glb file can also be loaded directly as the whole scene, camera included.
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, io::Error),
+    Import(PathBuf, String),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(path, err) => write!(f, "cannot read scene {}: {}", path.display(), err),
+            SceneError::Import(path, message) => write!(f, "cannot import {}: {}", path.display(), message),
+            SceneError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "scene error at line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl SceneError {
+    fn at(src: &str, offset: usize, message: String) -> Self {
+        //Converts a byte offset into 1-based line and column numbers
+        let before = &src[..offset.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |nl| nl + 1) + 1;
+        SceneError::Parse {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    camera: CameraDesc,
+    // Kept as raw tables so errors inside one can point at where it is defined
+    background: Option<Spanned<toml::Table>>,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<toml::Table>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<toml::Table>>,
+    #[serde(default)]
+    objects: Vec<Spanned<toml::Table>>,
+    #[serde(default)]
+    lights: Vec<Spanned<toml::Table>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Option<f64>,
+    image_width: Option<i32>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    min_bounces: Option<i32>,
+    vfov: Option<f64>,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    defocus_angle: Option<f64>,
+    focus_dist: Option<f64>,
+    shutter_open: Option<f64>,
+    shutter_close: Option<f64>,
+    mis: Option<HeuristicDesc>,
+    integrator: Option<IntegratorDesc>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum IntegratorDesc {
+    Path,
+    Normals,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum HeuristicDesc {
+    Balance,
+    Power,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDesc {
+    Solid { colour: [f64; 3] },
+    Gradient { bottom: [f64; 3], top: [f64; 3] },
+    Environment {
+        texture: Option<String>,
+        path: Option<PathBuf>,
+        rotation: Option<f64>,
+        intensity: Option<f64>,
+    },
+    Sky {
+        sun: [f64; 3],
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_sky_intensity")]
+        intensity: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: [f64; 3],
+        intensity: [f64; 3],
+        #[serde(default = "default_falloff")]
+        falloff: f64,
+    },
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        intensity: [f64; 3],
+        #[serde(default = "default_falloff")]
+        falloff: f64,
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    Directional {
+        direction: [f64; 3],
+        irradiance: [f64; 3],
+        #[serde(default)]
+        angular_diameter: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        colour: [f64; 3],
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        wrap: WrapDesc,
+    },
+    Checker {
+        size: f64,
+        even: AlbedoDesc,
+        odd: AlbedoDesc,
+    },
+    UvChecker {
+        columns: f64,
+        rows: f64,
+        even: AlbedoDesc,
+        odd: AlbedoDesc,
+    },
+    Perlin(NoiseDesc),
+    Turbulence(NoiseDesc),
+    Marble(NoiseDesc),
+    Wood(NoiseDesc),
+    Worley(NoiseDesc),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseDesc {
+    #[serde(default = "one")]
+    scale: f64,
+    #[serde(default)]
+    low: [f64; 3],
+    #[serde(default = "white")]
+    high: [f64; 3],
+    #[serde(default)]
+    seed: u64,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WrapDesc {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbedoDesc {
+    Colour([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Default { albedo: AlbedoDesc },
+    Lambertian { albedo: AlbedoDesc },
+    Metal { albedo: AlbedoDesc, fuzz: f64 },
+    Dielectric { ior: f64 },
+    DiffuseLight { emit: AlbedoDesc },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        centre: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        centre0: [f64; 3],
+        centre1: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "one")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: String,
+    },
+    Disk {
+        centre: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        a: [f64; 3],
+        b: [f64; 3],
+        c: [f64; 3],
+        material: String,
+    },
+    Mesh {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    Gltf {
+        path: PathBuf,
+    },
+}
+
+//Optional keys on any object, applied as scale, then rotate, then translate
+const TRANSFORM_KEYS: [&str; 3] = ["translate", "rotate", "scale"];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDesc {
+    translate: Option<[f64; 3]>,
+    rotate: Option<[f64; 3]>, //Degrees about x, then y, then z
+    scale: Option<ScaleDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f64),
+    PerAxis([f64; 3]),
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+fn default_sky_intensity() -> f64 {
+    0.05
+}
+
+//Inverse square, as real point lights fall off
+fn default_falloff() -> f64 {
+    2.0
+}
+
+fn white() -> [f64; 3] {
+    [1.0; 3]
+}
+
+fn typed<T: serde::de::DeserializeOwned>(src: &str, table: &toml::Table, offset: usize) -> Result<T, SceneError> {
+    toml::Value::Table(table.clone())
+        .try_into()
+        .map_err(|err: toml::de::Error| SceneError::at(src, offset, err.message().to_string()))
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3 {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+    }
+}
+
+//Textures can refer to each other (a checker of two noises), so they are built on
+//first use rather than in file order
+struct Textures<'a> {
+    src: &'a str,
+    base_dir: &'a Path,
+    tables: &'a BTreeMap<String, Spanned<toml::Table>>,
+    built: BTreeMap<String, Texture>,
+    in_progress: Vec<String>,
+}
+
+impl Textures<'_> {
+    //`offset` is where the referring table starts, for unknown names and cycles
+    fn albedo(&mut self, albedo: &AlbedoDesc, offset: usize) -> Result<Texture, SceneError> {
+        let name = match albedo {
+            AlbedoDesc::Colour(colour) => return Ok(Texture::Solid(vec3(*colour))),
+            AlbedoDesc::Texture(name) => name,
+        };
+        if let Some(texture) = self.built.get(name) {
+            return Ok(texture.clone());
+        }
+        let table = self
+            .tables
+            .get(name)
+            .ok_or_else(|| SceneError::at(self.src, offset, format!("unknown texture '{}'", name)))?;
+        if self.in_progress.contains(name) {
+            return Err(SceneError::at(self.src, offset, format!("texture '{}' refers to itself", name)));
+        }
+        self.in_progress.push(name.clone());
+        let texture = self.build(table)?;
+        self.in_progress.pop();
+        self.built.insert(name.clone(), texture.clone());
+        Ok(texture)
+    }
+
+    fn build(&mut self, table: &Spanned<toml::Table>) -> Result<Texture, SceneError> {
+        let offset = table.span().start;
+        let noise = |pattern, desc: NoiseDesc| {
+            let noise = NoiseTexture::new(pattern, desc.scale, vec3(desc.low), vec3(desc.high), desc.seed);
+            Texture::Noise(Arc::new(noise))
+        };
+        Ok(match typed(self.src, table.get_ref(), offset)? {
+            TextureDesc::Solid { colour } => Texture::Solid(vec3(colour)),
+            TextureDesc::Image { path, wrap } => {
+                let wrap = match wrap {
+                    WrapDesc::Repeat => WrapMode::Repeat,
+                    WrapDesc::Clamp => WrapMode::Clamp,
+                    WrapDesc::Mirror => WrapMode::Mirror,
+                };
+                let image = ImageTexture::load(&self.base_dir.join(path), wrap)
+                    .map_err(|err| SceneError::at(self.src, offset, err.to_string()))?;
+                Texture::Image(Arc::new(image))
+            }
+            TextureDesc::Checker { size, even, odd } => Texture::Checker {
+                inv_scale: 1.0 / size,
+                even: Arc::new(self.albedo(&even, offset)?),
+                odd: Arc::new(self.albedo(&odd, offset)?),
+            },
+            TextureDesc::UvChecker {
+                columns,
+                rows,
+                even,
+                odd,
+            } => Texture::UvChecker {
+                columns,
+                rows,
+                even: Arc::new(self.albedo(&even, offset)?),
+                odd: Arc::new(self.albedo(&odd, offset)?),
+            },
+            TextureDesc::Perlin(desc) => noise(NoisePattern::Perlin, desc),
+            TextureDesc::Turbulence(desc) => noise(NoisePattern::Turbulence, desc),
+            TextureDesc::Marble(desc) => noise(NoisePattern::Marble, desc),
+            TextureDesc::Wood(desc) => noise(NoisePattern::Wood, desc),
+            TextureDesc::Worley(desc) => noise(NoisePattern::Worley, desc),
+        })
+    }
+}
+
+impl MaterialDesc {
+    fn build(&self, textures: &mut Textures, offset: usize) -> Result<Material, SceneError> {
+        Ok(match self {
+            MaterialDesc::Default { albedo } => Material::Default {
+                albedo: textures.albedo(albedo, offset)?,
+            },
+            MaterialDesc::Lambertian { albedo } => Material::Lambertian {
+                albedo: textures.albedo(albedo, offset)?,
+            },
+            MaterialDesc::Metal { albedo, fuzz } => Material::Metal {
+                albedo: textures.albedo(albedo, offset)?,
+                fuzz: *fuzz,
+            },
+            MaterialDesc::Dielectric { ior } => Material::Dielectric { idx_refract: *ior },
+            MaterialDesc::DiffuseLight { emit } => Material::DiffuseLight {
+                emit: textures.albedo(emit, offset)?,
+            },
+        })
+    }
+}
+
+impl BackgroundDesc {
+    fn build(&self, textures: &mut Textures, offset: usize) -> Result<Background, SceneError> {
+        Ok(match self {
+            BackgroundDesc::Solid { colour } => Background::Solid(vec3(*colour)),
+            BackgroundDesc::Gradient { bottom, top } => Background::Gradient {
+                bottom: vec3(*bottom),
+                top: vec3(*top),
+            },
+            BackgroundDesc::Environment {
+                texture,
+                path,
+                rotation,
+                intensity,
+            } => match (texture, path) {
+                (Some(_), _) if rotation.is_some() || intensity.is_some() => {
+                    return Err(SceneError::at(
+                        textures.src,
+                        offset,
+                        "rotation and intensity need the environment given by path".to_string(),
+                    ))
+                }
+                (Some(name), None) => {
+                    Background::Environment(textures.albedo(&AlbedoDesc::Texture(name.clone()), offset)?)
+                }
+                (None, Some(path)) => {
+                    let image = ImageTexture::load(&textures.base_dir.join(path), WrapMode::Repeat)
+                        .map_err(|err| SceneError::at(textures.src, offset, err.to_string()))?;
+                    let light = EnvironmentLight::new(image, rotation.unwrap_or(0.0), intensity.unwrap_or(1.0));
+                    Background::EnvironmentLight(Arc::new(light))
+                }
+                _ => {
+                    return Err(SceneError::at(
+                        textures.src,
+                        offset,
+                        "an environment needs either a texture or a path".to_string(),
+                    ))
+                }
+            },
+            BackgroundDesc::Sky {
+                sun,
+                turbidity,
+                intensity,
+            } => Background::Sky(Sky::new(vec3(*sun), *turbidity, *intensity)),
+        })
+    }
+}
+
+impl LightDesc {
+    fn build(&self) -> Light {
+        match *self {
+            LightDesc::Point {
+                position,
+                intensity,
+                falloff,
+            } => Light::Point {
+                position: vec3(position),
+                intensity: vec3(intensity),
+                falloff,
+            },
+            LightDesc::Spot {
+                position,
+                direction,
+                intensity,
+                falloff,
+                inner_angle,
+                outer_angle,
+            } => Light::Spot {
+                position: vec3(position),
+                direction: vec3(direction),
+                intensity: vec3(intensity),
+                falloff,
+                inner_angle,
+                outer_angle,
+            },
+            LightDesc::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => Light::Directional {
+                direction: vec3(direction),
+                irradiance: vec3(irradiance),
+                angular_diameter,
+            },
+        }
+    }
+}
+
+impl TransformDesc {
+    fn build(&self) -> Option<Mat4> {
+        if self.translate.is_none() && self.rotate.is_none() && self.scale.is_none() {
+            return None;
+        }
+        let scale = match self.scale {
+            Some(ScaleDesc::Uniform(s)) => [s, s, s],
+            Some(ScaleDesc::PerAxis(s)) => s,
+            None => [1.0, 1.0, 1.0],
+        };
+        let [rx, ry, rz] = self.rotate.unwrap_or([0.0; 3]);
+        let axis = |x, y, z| Vec3 { x, y, z };
+        Some(
+            Mat4::translation(vec3(self.translate.unwrap_or([0.0; 3])))
+                * Mat4::rotation(axis(0.0, 0.0, 1.0), rz)
+                * Mat4::rotation(axis(0.0, 1.0, 0.0), ry)
+                * Mat4::rotation(axis(1.0, 0.0, 0.0), rx)
+                * Mat4::scaling(vec3(scale)),
+        )
+    }
+}
+
+impl CameraDesc {
+    fn build(&self) -> Camera {
+        let mut camera = Camera::new(
+            self.aspect_ratio.unwrap_or(1.0),
+            self.image_width.unwrap_or(800),
+            self.samples_per_pixel.unwrap_or(10),
+        );
+        camera.max_depth = self.max_depth.unwrap_or(camera.max_depth);
+        camera.min_bounces = self.min_bounces.unwrap_or(camera.min_bounces);
+        camera.vfov = self.vfov.unwrap_or(camera.vfov);
+        camera.lookfrom = self.lookfrom.map_or(camera.lookfrom, vec3);
+        camera.lookat = self.lookat.map_or(camera.lookat, vec3);
+        camera.vup = self.vup.map_or(camera.vup, vec3);
+        camera.defocus_angle = self.defocus_angle.unwrap_or(camera.defocus_angle);
+        camera.focus_dist = self.focus_dist;
+        camera.shutter_open = self.shutter_open.unwrap_or(camera.shutter_open);
+        camera.shutter_close = self.shutter_close.unwrap_or(camera.shutter_close);
+        camera.mis_heuristic = match self.mis {
+            Some(HeuristicDesc::Balance) => MisHeuristic::Balance,
+            Some(HeuristicDesc::Power) => MisHeuristic::Power,
+            None => camera.mis_heuristic,
+        };
+        camera.integrator = match self.integrator {
+            Some(IntegratorDesc::Path) => IntegratorKind::Path,
+            Some(IntegratorDesc::Normals) => IntegratorKind::Normals,
+            None => camera.integrator,
+        };
+        camera
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<(HittableList, Camera), SceneError> {
+    let ext = path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase);
+    if let Some("gltf" | "glb") = ext.as_deref() {
+        return load_gltf(path);
+    }
+    let src = fs::read_to_string(path).map_err(|err| SceneError::Io(path.to_path_buf(), err))?;
+    parse_scene(&src, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Builds a scene from TOML source. Relative file paths inside it are resolved against `base_dir`.
+pub fn parse_scene(src: &str, base_dir: &Path) -> Result<(HittableList, Camera), SceneError> {
+    let desc: SceneDesc = toml::from_str(src).map_err(|err| {
+        let offset = err.span().map_or(0, |span| span.start);
+        SceneError::at(src, offset, err.message().to_string())
+    })?;
+
+    let mut textures = Textures {
+        src,
+        base_dir,
+        tables: &desc.textures,
+        built: BTreeMap::new(),
+        in_progress: Vec::new(),
+    };
+    // Unused textures are still built, so mistakes in them are reported
+    for name in desc.textures.keys() {
+        textures.albedo(&AlbedoDesc::Texture(name.clone()), 0)?;
+    }
+    let background = match &desc.background {
+        Some(table) => {
+            let offset = table.span().start;
+            let background: BackgroundDesc = typed(src, table.get_ref(), offset)?;
+            Some(background.build(&mut textures, offset)?)
+        }
+        None => None,
+    };
+    let mut materials: BTreeMap<&str, Material> = BTreeMap::new();
+    for (name, table) in &desc.materials {
+        let offset = table.span().start;
+        let mat: MaterialDesc = typed(src, table.get_ref(), offset)?;
+        materials.insert(name.as_str(), mat.build(&mut textures, offset)?);
+    }
+    let lookup = |name: &str, offset: usize| {
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SceneError::at(src, offset, format!("unknown material '{}'", name)))
+    };
+
+    // Files are loaded once and shared by every object naming them, so a mesh placed
+    // many times with different transforms is only stored once
+    let mut loaded: HashMap<(PathBuf, Option<String>), Arc<dyn Hittable>> = HashMap::new();
+    let mut world = HittableList::new();
+    for table in &desc.objects {
+        let offset = table.span().start;
+        let mut fields = table.get_ref().clone();
+        let mut placement = toml::Table::new();
+        for key in TRANSFORM_KEYS {
+            if let Some(value) = fields.remove(key) {
+                placement.insert(key.to_string(), value);
+            }
+        }
+        let placement: TransformDesc = typed(src, &placement, offset)?;
+
+        let object: Arc<dyn Hittable> = match typed(src, &fields, offset)? {
+            ObjectDesc::Sphere {
+                centre,
+                radius,
+                material,
+            } => Arc::new(Sphere {
+                centre: vec3(centre),
+                radius,
+                mat_type: lookup(&material, offset)?,
+            }),
+            ObjectDesc::MovingSphere {
+                centre0,
+                centre1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Arc::new(MovingSphere {
+                centre0: vec3(centre0),
+                centre1: vec3(centre1),
+                time0,
+                time1,
+                radius,
+                mat_type: lookup(&material, offset)?,
+            }),
+            ObjectDesc::Quad { q, u, v, material } => Arc::new(Quad::new(
+                vec3(q),
+                vec3(u),
+                vec3(v),
+                lookup(&material, offset)?,
+            )),
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material,
+            } => Arc::new(Plane::new(vec3(point), vec3(normal), lookup(&material, offset)?)),
+            ObjectDesc::Disk {
+                centre,
+                normal,
+                radius,
+                material,
+            } => Arc::new(Disk::new(
+                vec3(centre),
+                vec3(normal),
+                radius,
+                lookup(&material, offset)?,
+            )),
+            ObjectDesc::Triangle { a, b, c, material } => Arc::new(Triangle {
+                a: vec3(a),
+                b: vec3(b),
+                c: vec3(c),
+                mat_type: lookup(&material, offset)?,
+            }),
+            ObjectDesc::Mesh { path, material } => match loaded.get(&(path.clone(), material.clone())) {
+                Some(mesh) => mesh.clone(),
+                None => {
+                    let fallback = match &material {
+                        Some(name) => Some(lookup(name, offset)?),
+                        None => None,
+                    };
+                    let data = load_mesh(&base_dir.join(&path), fallback)
+                        .map_err(|err| SceneError::at(src, offset, err.to_string()))?;
+                    let mesh: Arc<dyn Hittable> = Arc::new(TriangleMesh::new(data));
+                    loaded.insert((path, material), mesh.clone());
+                    mesh
+                }
+            },
+            ObjectDesc::Gltf { path } => match loaded.get(&(path.clone(), None)) {
+                Some(objects) => objects.clone(),
+                None => {
+                    let (objects, _) = load_gltf(&base_dir.join(&path))
+                        .map_err(|err| SceneError::at(src, offset, err.to_string()))?;
+                    let objects: Arc<dyn Hittable> = Arc::new(BvhNode::new(objects));
+                    loaded.insert((path, None), objects.clone());
+                    objects
+                }
+            },
+        };
+        match placement.build() {
+            Some(matrix) => world.push(Arc::new(Transform::new(object, matrix))),
+            None => world.push(object),
+        }
+    }
+
+    for table in &desc.lights {
+        let light: LightDesc = typed(src, table.get_ref(), table.span().start)?;
+        world.lights.push(light.build());
+    }
+
+    let mut camera = desc.camera.build();
+    camera.background = background.unwrap_or_default();
+    Ok((world, camera))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(src: &str) -> (usize, usize, String) {
+        match parse_scene(src, Path::new("")) {
+            Err(SceneError::Parse { line, column, message }) => (line, column, message),
+            Err(err) => panic!("expected a parse error, got {}", err),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn parses_a_minimal_scene() {
+        let src = "\
+[materials.ground]
+type = \"lambertian\"
+albedo = [0.8, 0.8, 0.0]
+
+[[objects]]
+type = \"sphere\"
+centre = [0.0, -100.5, -1.0]
+radius = 100.0
+material = \"ground\"
+";
+        let (world, _) = parse_scene(src, Path::new("")).unwrap();
+        assert_eq!(world.objects.len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_carry_line_and_column() {
+        let (line, column, _) = parse_error("[camera]\nlookfrom = [0.0, 1.0\n");
+        assert_eq!((line, column), (2, 21));
+    }
+
+    #[test]
+    fn unknown_material_points_at_its_object() {
+        let src = "\
+[materials.ground]
+type = \"lambertian\"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = \"sphere\"
+centre = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"missing\"
+";
+        let (line, column, message) = parse_error(src);
+        assert_eq!(message, "unknown material 'missing'");
+        assert_eq!((line, column), (5, 1));
+    }
+
+    #[test]
+    fn unknown_fields_point_at_their_table() {
+        let src = "\
+[camera]
+vfov = 40.0
+
+[materials.shiny]
+type = \"metal\"
+albedo = [0.8, 0.8, 0.8]
+fuzzz = 0.1
+";
+        let (line, column, message) = parse_error(src);
+        assert!(message.contains("fuzzz"), "{}", message);
+        assert_eq!((line, column), (4, 1));
+    }
+
+    #[test]
+    fn offsets_convert_to_one_based_positions() {
+        let src = "ab\ncd\n";
+        for (offset, expected) in [(0, (1, 1)), (1, (1, 2)), (3, (2, 1)), (4, (2, 2)), (100, (3, 1))] {
+            match SceneError::at(src, offset, String::new()) {
+                SceneError::Parse { line, column, .. } => assert_eq!((line, column), expected),
+                err => panic!("{}", err),
+            }
+        }
+    }
+}
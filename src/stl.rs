@@ -0,0 +1,182 @@
+use crate::{
+    material::Material,
+    mesh::{MeshData, MeshError},
+    vec3::Vec3,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+//STL import, binary or ASCII. STL stores every triangle with its own copies of
+//the corner positions, so identical positions are merged into shared vertices.
+//Facet normals are ignored: the winding gives the same flat normal.
+
+fn add_vertex(mesh: &mut MeshData, seen: &mut HashMap<[u64; 3], u32>, p: Vec3) -> u32 {
+    let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    *seen.entry(key).or_insert_with(|| {
+        mesh.positions.push(p);
+        (mesh.positions.len() - 1) as u32
+    })
+}
+
+pub fn load_stl(path: &Path, material: Material) -> Result<MeshData, MeshError> {
+    let data = fs::read(path).map_err(|err| MeshError::Io(path.to_path_buf(), err))?;
+    let mut mesh = MeshData {
+        materials: vec![material],
+        ..Default::default()
+    };
+    let mut seen: HashMap<[u64; 3], u32> = HashMap::new();
+
+    // Binary files are recognised by size, since many of them also start with "solid"
+    let binary_count = match data.len() >= 84 {
+        true => Some(u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize),
+        false => None,
+    };
+    if let Some(count) = binary_count.filter(|count| data.len() == 84 + count * 50) {
+        for facet in data[84..].chunks_exact(50) {
+            let mut corners = [0u32; 3];
+            for (k, corner) in corners.iter_mut().enumerate() {
+                // 12 bytes of normal, then three vertices of three f32 each
+                let at = |i: usize| {
+                    let o = 12 + k * 12 + i * 4;
+                    f32::from_le_bytes([facet[o], facet[o + 1], facet[o + 2], facet[o + 3]]) as f64
+                };
+                *corner = add_vertex(&mut mesh, &mut seen, Vec3 {
+                    x: at(0),
+                    y: at(1),
+                    z: at(2),
+                });
+            }
+            mesh.indices.push(corners);
+            mesh.face_materials.push(0);
+        }
+        debug_assert_eq!(mesh.indices.len(), count);
+        return Ok(mesh);
+    }
+
+    let src = String::from_utf8_lossy(&data);
+    let error = |line: usize, message: String| MeshError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+    let mut corners: Vec<u32> = Vec::with_capacity(3);
+    let mut last_line = 0;
+    for (n, raw) in src.lines().enumerate() {
+        last_line = n + 1;
+        let tokens: Vec<&str> = raw.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("vertex") => {
+                if tokens.len() != 4 {
+                    return Err(error(n + 1, "vertex needs three coordinates".to_string()));
+                }
+                let mut xyz = [0.0; 3];
+                for (value, token) in xyz.iter_mut().zip(&tokens[1..]) {
+                    *value = token
+                        .parse()
+                        .map_err(|_| error(n + 1, format!("'{}' is not a number", token)))?;
+                }
+                if corners.len() == 3 {
+                    return Err(error(n + 1, "facet has more than three vertices".to_string()));
+                }
+                corners.push(add_vertex(&mut mesh, &mut seen, Vec3 {
+                    x: xyz[0],
+                    y: xyz[1],
+                    z: xyz[2],
+                }));
+            }
+            Some("endfacet") => {
+                if corners.len() != 3 {
+                    return Err(error(n + 1, format!("facet has {} vertices, expected 3", corners.len())));
+                }
+                mesh.indices.push([corners[0], corners[1], corners[2]]);
+                mesh.face_materials.push(0);
+                corners.clear();
+            }
+            _ => {} // solid, facet normal, outer loop, endloop, endsolid
+        }
+    }
+    if mesh.indices.is_empty() && !src.trim_start().starts_with("solid") {
+        return Err(error(last_line, "neither a binary nor an ASCII STL file".to_string()));
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{default_mesh_material, load_temp};
+
+    fn load(name: &str, contents: &[u8]) -> Result<MeshData, MeshError> {
+        load_temp(name, contents, |path| load_stl(path, default_mesh_material(0.5)))
+    }
+
+    fn binary(header: &[u8], facets: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut data = header.to_vec();
+        data.resize(80, b' ');
+        data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            data.extend_from_slice(&[0; 12]); //Normal, ignored
+            for value in facet.iter().flatten() {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&[0; 2]); //Attribute byte count
+        }
+        data
+    }
+
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    #[test]
+    fn binary_files_merge_shared_corners() {
+        let mesh = load("binary.stl", &binary(b"exported square", &SQUARE)).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn binary_files_starting_with_solid_are_still_binary() {
+        let mesh = load("solid_binary.stl", &binary(b"solid square", &SQUARE)).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+    }
+
+    #[test]
+    fn ascii_files_are_detected_by_size() {
+        let src = "\
+solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+        let mesh = load("ascii.stl", src.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        // A binary count that does not match the size falls back to ASCII parsing
+        let mut data = binary(b"square", &SQUARE);
+        data.pop();
+        assert!(load("short.stl", &data).is_err());
+        match load("two_vertices.stl", b"solid t\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\n") {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(line, 7),
+            _ => panic!("a facet with two vertices should not load"),
+        }
+    }
+}
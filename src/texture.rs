@@ -0,0 +1,403 @@
+use crate::{
+    colour::{gamma_to_linear, Colour},
+    hdr::read_hdr,
+    noise::{worley, Perlin},
+    vec3::Vec3,
+};
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+//Colour sources for material albedo, looked up with the (u, v) surface coordinates
+//primitives write into HitRecord or, for procedural ones, the world space hit point.
+//Anything large is shared behind an Arc so materials stay cheap to clone.
+
+#[derive(Clone, Debug)]
+pub enum Texture {
+    Solid(Colour),
+    Image(Arc<ImageTexture>),
+    Checker {
+        inv_scale: f64, //Cells per unit length
+        even: Arc<Texture>,
+        odd: Arc<Texture>,
+    },
+    UvChecker {
+        columns: f64, //Cells across u
+        rows: f64,    //Cells across v
+        even: Arc<Texture>,
+        odd: Arc<Texture>,
+    },
+    Noise(Arc<NoiseTexture>),
+}
+
+impl Default for Texture {
+    fn default() -> Self {
+        Texture::Solid(Colour::default())
+    }
+}
+
+impl From<Colour> for Texture {
+    fn from(colour: Colour) -> Self {
+        Texture::Solid(colour)
+    }
+}
+
+impl Texture {
+    pub fn value(&self, u: f64, v: f64, p: &Vec3) -> Colour {
+        match self {
+            Texture::Solid(colour) => *colour,
+            Texture::Image(image) => image.sample(u, v),
+            Texture::Checker { inv_scale, even, odd } => {
+                let cell = (inv_scale * p.x).floor() + (inv_scale * p.y).floor() + (inv_scale * p.z).floor();
+                match cell as i64 % 2 == 0 {
+                    true => even.value(u, v, p),
+                    false => odd.value(u, v, p),
+                }
+            }
+            Texture::UvChecker {
+                columns,
+                rows,
+                even,
+                odd,
+            } => {
+                let cell = (u * columns).floor() + (v * rows).floor();
+                match cell as i64 % 2 == 0 {
+                    true => even.value(u, v, p),
+                    false => odd.value(u, v, p),
+                }
+            }
+            Texture::Noise(noise) => noise.value(p),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoisePattern {
+    Perlin,     //Raw gradient noise
+    Turbulence, //fBm sum of noise octaves, cloudy
+    Marble,     //Sine stripes along z, bent by turbulence
+    Wood,       //Rings around the y axis, wobbled by turbulence
+    Worley,     //Distance to the nearest cell point, stones and scales
+}
+
+/// Blends between two colours by a noise pattern in [0, 1] evaluated at
+/// `scale` times the hit point.
+pub struct NoiseTexture {
+    pub(crate) pattern: NoisePattern,
+    pub(crate) scale: f64,
+    pub(crate) low: Colour,
+    pub(crate) high: Colour,
+    perlin: Perlin,
+    seed: u64,
+}
+
+impl fmt::Debug for NoiseTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NoiseTexture({:?}, scale {})", self.pattern, self.scale)
+    }
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, scale: f64, low: Colour, high: Colour, seed: u64) -> Self {
+        NoiseTexture {
+            pattern,
+            scale,
+            low,
+            high,
+            perlin: Perlin::new(seed),
+            seed,
+        }
+    }
+
+    pub fn value(&self, p: &Vec3) -> Colour {
+        const OCTAVES: u32 = 7;
+        let s = self.scale * *p;
+        let t = match self.pattern {
+            NoisePattern::Perlin => 0.5 * (1.0 + self.perlin.noise(&s)),
+            NoisePattern::Turbulence => self.perlin.turbulence(&s, OCTAVES),
+            NoisePattern::Marble => 0.5 * (1.0 + (s.z + 10.0 * self.perlin.turbulence(p, OCTAVES)).sin()),
+            NoisePattern::Wood => {
+                let rings = (s.x * s.x + s.z * s.z).sqrt() + 2.0 * self.perlin.turbulence(&(0.5 * s), OCTAVES);
+                rings - rings.floor()
+            }
+            NoisePattern::Worley => worley(&s, self.seed),
+        };
+        let t = t.clamp(0.0, 1.0);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+/// What happens to texture coordinates outside [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn wrap(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(PathBuf, io::Error),
+    Decode(PathBuf, String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(path, err) => write!(f, "cannot read image {}: {}", path.display(), err),
+            ImageError::Decode(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Linear RGB pixels, top row first, with (u, v) = (0, 0) at the bottom left.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    wrap: WrapMode,
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ImageTexture({}x{}, {:?})", self.width, self.height, self.wrap)
+    }
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Colour>, wrap: WrapMode) -> Self {
+        assert_eq!(pixels.len(), width * height, "one pixel per texel");
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap,
+        }
+    }
+
+    /// Reads a PNG, PPM (P3/P6) or Radiance HDR file. 8 and 16-bit images are
+    /// taken to be gamma encoded like this renderer's own output, HDR files as linear.
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<Self, ImageError> {
+        let data = fs::read(path).map_err(|err| ImageError::Io(path.to_path_buf(), err))?;
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        let decoded = match ext.as_deref() {
+            Some("png") => decode_png(&data),
+            Some("ppm" | "pnm") => decode_ppm(&data),
+            Some("hdr") => read_hdr(&data).map(|(w, h, pixels)| {
+                let pixels = pixels.iter().map(|&[r, g, b]| Colour {
+                    x: r as f64,
+                    y: g as f64,
+                    z: b as f64,
+                });
+                (w, h, pixels.collect())
+            }),
+            _ => Err("unsupported image format, expected .png, .ppm or .hdr".to_string()),
+        };
+        let (width, height, pixels) = decoded.map_err(|message| ImageError::Decode(path.to_path_buf(), message))?;
+        if width == 0 || height == 0 {
+            return Err(ImageError::Decode(path.to_path_buf(), "image is empty".to_string()));
+        }
+        Ok(ImageTexture::new(width, height, pixels, wrap))
+    }
+
+    /// A copy with every pixel multiplied by `tint`, for formats where a constant
+    /// colour scales the texture.
+    pub fn tinted(&self, tint: &Colour) -> Self {
+        ImageTexture {
+            pixels: self.pixels.iter().map(|p| p.element_wise_multiply(tint)).collect(),
+            ..*self
+        }
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Texel (x, y) counted from the top left, wrapped into the image.
+    pub fn texel(&self, x: i64, y: i64) -> Colour {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinear filtering between the four nearest texel centres.
+    pub fn sample(&self, u: f64, v: f64) -> Colour {
+        let fx = u * self.width as f64 - 0.5;
+        let fy = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
+
+type Decoded = (usize, usize, Vec<Colour>);
+
+fn decode_png(data: &[u8]) -> Result<Decoded, String> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    // Palettes and low bit depths come out as 8-bit RGB(A) or grey
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size().ok_or("image is too large")?];
+    let info = reader.next_frame(&mut buf).map_err(|err| err.to_string())?;
+    let channels = info.color_type.samples();
+    let wide = info.bit_depth == png::BitDepth::Sixteen;
+    let max = if wide { 65535.0 } else { 255.0 };
+    let sample = |i: usize| match wide {
+        true => u16::from_be_bytes([buf[2 * i], buf[2 * i + 1]]) as f64,
+        false => buf[i] as f64,
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = y * info.line_size / if wide { 2 } else { 1 };
+        for x in 0..width {
+            let at = row + x * channels;
+            // Grey images (with or without alpha) repeat their one channel
+            let (r, g, b) = match channels {
+                1 | 2 => (at, at, at),
+                _ => (at, at + 1, at + 2),
+            };
+            pixels.push(Colour {
+                x: gamma_to_linear(sample(r) / max),
+                y: gamma_to_linear(sample(g) / max),
+                z: gamma_to_linear(sample(b) / max),
+            });
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+fn decode_ppm(data: &[u8]) -> Result<Decoded, String> {
+    //Header fields are whitespace separated and may be interleaved with # comments
+    let mut pos = 0;
+    let mut field = || -> Result<String, String> {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).is_some_and(|&b| b != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                Some(_) => break,
+                None => return Err("header is truncated".to_string()),
+            }
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&data[start..pos]).into_owned())
+    };
+    let magic = field()?;
+    let mut number = || -> Result<usize, String> {
+        let text = field()?;
+        text.parse().map_err(|_| format!("'{}' is not a number", text))
+    };
+    let (width, height, max) = (number()?, number()?, number()?);
+    if !(1..=65535).contains(&max) {
+        return Err(format!("maximum value {} is outside 1..65535", max));
+    }
+
+    // Corrupt headers can claim sizes that do not fit in memory, or in a usize
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| format!("image size {}x{} is too large", width, height))?;
+    let samples: Vec<usize> = match magic.as_str() {
+        "P3" => (0..count).map(|_| number()).collect::<Result<_, _>>()?,
+        "P6" => {
+            // A single whitespace byte separates the header from the raster
+            let raster = data.get(pos + 1..).unwrap_or_default();
+            let bytes = if max > 255 { 2 } else { 1 };
+            if raster.len() / bytes < count {
+                return Err("pixel data is truncated".to_string());
+            }
+            match bytes {
+                2 => raster
+                    .chunks_exact(2)
+                    .take(count)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]) as usize)
+                    .collect(),
+                _ => raster[..count].iter().map(|&b| b as usize).collect(),
+            }
+        }
+        _ => return Err(format!("unsupported PPM type '{}', expected P3 or P6", magic)),
+    };
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|rgb| Colour {
+            x: gamma_to_linear(rgb[0] as f64 / max as f64),
+            y: gamma_to_linear(rgb[1] as f64 / max as f64),
+            z: gamma_to_linear(rgb[2] as f64 / max as f64),
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_ascii_and_binary_ppm() {
+        let (width, height, pixels) = decode_ppm(b"P3\n# a comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_eq!((pixels[0].x, pixels[0].z), (1.0, 0.0));
+        assert_eq!((pixels[1].x, pixels[1].z), (0.0, 1.0));
+
+        let mut p6 = b"P6 1 1 65535\n".to_vec();
+        p6.extend_from_slice(&[0xff, 0xff, 0, 0, 0xff, 0xff]);
+        let (_, _, pixels) = decode_ppm(&p6).unwrap();
+        assert_eq!((pixels[0].x, pixels[0].y, pixels[0].z), (1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_headers() {
+        for (data, message) in [
+            (&b"P6\n4 4"[..], "header is truncated"),
+            (b"P6\n4 4\n255\n\0\0\0", "pixel data is truncated"),
+            (b"P3\n1 1\n255\n0 0", "header is truncated"),
+            (b"P6\n18446744073709551615 2\n255\n\0", "is too large"),
+            (b"P6\n4294967296 4294967296\n255\n\0", "is too large"),
+            (b"P6\n1 1\n0\n\0\0\0", "outside 1..65535"),
+            (b"P5\n1 1\n255\n\0", "unsupported PPM type"),
+        ] {
+            match decode_ppm(data) {
+                Err(err) => assert!(err.contains(message), "{:?}: {}", String::from_utf8_lossy(data), err),
+                Ok(_) => panic!("{:?} should not decode", String::from_utf8_lossy(data)),
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    aabb::{Aabb, UNIVERSE_AABB},
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    light::Light,
+    math::{Mat3, Normal3, Point3, Quat},
+    ray::{Ray, RayProperties},
+    validate::Report,
+    vec3::{Vec3, VectorProperties},
+};
+use std::ops;
+use std::sync::Arc;
+
+//Affine 4x4 matrix acting on column vectors, m[row][column]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4 {
+    pub(crate) m: [[f64; 4]; 4],
+}
+
+pub const IDENTITY: Mat4 = Mat4 {
+    m: [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ],
+};
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub fn translation(offset: Vec3) -> Self {
+        let mut out = IDENTITY;
+        out.m[0][3] = offset.x;
+        out.m[1][3] = offset.y;
+        out.m[2][3] = offset.z;
+        out
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut out = IDENTITY;
+        out.m[0][0] = factors.x;
+        out.m[1][1] = factors.y;
+        out.m[2][2] = factors.z;
+        out
+    }
+
+    /// Counter-clockwise rotation by `degrees` looking down `axis` towards the origin.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        Mat4::from(Quat::from_axis_angle(axis, degrees))
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, None for a singular matrix.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][col];
+                if row != col && factor != 0.0 {
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Mat4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        self.transform_vector(p)
+            + Vec3 {
+                x: self.m[0][3],
+                y: self.m[1][3],
+                z: self.m[2][3],
+            }
+    }
+
+    //Ignores the translation, for directions and offsets
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    //Composes the two, `a * b` applies b first
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = IDENTITY;
+        for (r, row) in out.m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[r][k] * rhs.m[k][c]).sum();
+            }
+        }
+        out
+    }
+}
+
+//Places a shared object in the world through an affine matrix. Rays are taken into
+//object space, so the object is intersected as if untransformed, and the hit is
+//brought back out. Many Transforms can share one object, e.g. a mesh loaded once.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    matrix: Mat4,
+    inverse: Option<Mat4>, //None when the matrix is singular, which validate reports
+    normal_matrix: Mat3,
+    det: f64, //Of the linear part, for scaling areas
+    bbox: Aabb,
+}
+
+impl Transform {
+    pub fn new(object: Arc<dyn Hittable>, matrix: Mat4) -> Self {
+        let inner = object.bounding_box();
+        let bbox = match inner.is_bounded() {
+            false => UNIVERSE_AABB,
+            true => {
+                //Box around the eight transformed corners
+                let mut bbox = Aabb::default();
+                for i in 0..8 {
+                    let corner = Vec3 {
+                        x: if i & 1 == 0 { inner.x.min } else { inner.x.max },
+                        y: if i & 2 == 0 { inner.y.min } else { inner.y.max },
+                        z: if i & 4 == 0 { inner.z.min } else { inner.z.max },
+                    };
+                    let p = (matrix * Point3(corner)).into();
+                    bbox = Aabb::surrounding(&bbox, &Aabb::from_points(p, p));
+                }
+                bbox
+            }
+        };
+        Transform {
+            object,
+            matrix,
+            inverse: matrix.inverse(),
+            normal_matrix: matrix.normal_matrix().unwrap_or_default(),
+            det: matrix.upper_left().determinant(),
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut Option<HitRecord>) -> bool {
+        let inverse = match &self.inverse {
+            Some(inverse) => inverse,
+            None => return false,
+        };
+        // The direction is not renormalised, so t means the same in both spaces
+        let local = Ray {
+            origin: (*inverse * Point3(r.origin())).into(),
+            direction: *inverse * r.direction(),
+            time: r.time(),
+        };
+        if !self.object.hit(&local, ray_t, rec) {
+            return false;
+        }
+        if let Some(hit) = rec {
+            // Normals go through the inverse transpose. This keeps their sign relative
+            // to the ray direction, so front_face still holds.
+            // A patch of surface grows by |det| times the length of its transformed
+            // normal, which thins out the density of light samples on it
+            let n = (self.normal_matrix * Normal3(hit.n)).0;
+            hit.p = (self.matrix * Point3(hit.p)).into();
+            hit.area_pdf /= self.det.abs() * n.d_euclid();
+            hit.n = n.unit();
+        }
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn validate(&self, report: &mut Report) {
+        if self.inverse.is_none() {
+            report.error("transform matrix is singular, the object would be flattened".to_string());
+        }
+        self.object.validate(report);
+    }
+
+    fn lights(&self) -> Vec<Light> {
+        self.object.lights().iter().map(|light| light.transformed(&self.matrix)).collect()
+    }
+}
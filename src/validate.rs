@@ -0,0 +1,384 @@
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::Material;
+use crate::texture::Texture;
+use crate::vec3::{Vec3, VectorProperties};
+use std::fmt;
+
+//Sanity checks for built scenes. Errors are inputs the renderer cannot handle
+//(NaN basis vectors, empty images), warnings are physically implausible but renderable.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+pub fn fmt_vec3(v: &Vec3) -> String {
+    format!("({}, {}, {})", v.x, v.y, v.z)
+}
+
+impl Report {
+    pub fn warn(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    pub fn check_albedo(&mut self, albedo: &Vec3, context: &str) {
+        let channels = [albedo.x, albedo.y, albedo.z];
+        if channels.iter().any(|c| !c.is_finite() || *c < 0.0) {
+            self.error(format!("{}: albedo {} has negative or non-finite channels", context, fmt_vec3(albedo)));
+        } else if channels.iter().any(|c| *c > 1.0) {
+            self.warn(format!(
+                "{}: albedo {} is above 1 and reflects more light than it receives",
+                context,
+                fmt_vec3(albedo)
+            ));
+        }
+    }
+
+    //Emitted and background light may well exceed 1, only its sign is checked
+    pub fn check_radiance(&mut self, radiance: &Vec3, context: &str) {
+        let channels = [radiance.x, radiance.y, radiance.z];
+        if channels.iter().any(|c| !c.is_finite() || *c < 0.0) {
+            self.error(format!("{}: radiance {} has negative or non-finite channels", context, fmt_vec3(radiance)));
+        }
+    }
+
+    //Image textures are not checked texel by texel
+    pub fn check_texture(&mut self, texture: &Texture, context: &str) {
+        match texture {
+            Texture::Solid(albedo) => self.check_albedo(albedo, context),
+            Texture::Image(_) => {}
+            Texture::Checker { inv_scale, even, odd } => {
+                if *inv_scale <= 0.0 || !inv_scale.is_finite() {
+                    self.error(format!("{}: checker cells must have a positive size", context));
+                }
+                self.check_texture(even, context);
+                self.check_texture(odd, context);
+            }
+            Texture::UvChecker { even, odd, .. } => {
+                self.check_texture(even, context);
+                self.check_texture(odd, context);
+            }
+            Texture::Noise(noise) => {
+                self.check_albedo(&noise.low, context);
+                self.check_albedo(&noise.high, context);
+            }
+        }
+    }
+
+    pub fn check_material(&mut self, material: &Material, context: &str) {
+        match material {
+            Material::Default { albedo } | Material::Lambertian { albedo } => self.check_texture(albedo, context),
+            Material::Metal { albedo, fuzz } => {
+                self.check_texture(albedo, context);
+                if !(0.0..=1.0).contains(fuzz) {
+                    self.warn(format!("{}: metal fuzz {} is outside [0, 1]", context, fuzz));
+                }
+            }
+            Material::Dielectric { idx_refract } => {
+                if *idx_refract <= 0.0 || !idx_refract.is_finite() {
+                    self.error(format!("{}: index of refraction {} must be positive", context, idx_refract));
+                }
+            }
+            //Only solid emitters are checked, textured ones are left alone
+            Material::DiffuseLight { emit } => {
+                if let Texture::Solid(emit) = emit {
+                    self.check_radiance(emit, context);
+                    if emit.near_zero() {
+                        self.warn(format!("{}: light emits nothing", context));
+                    }
+                }
+            }
+        }
+    }
+
+    //Area lights are checked through the material of the object they come from
+    pub fn check_light(&mut self, light: &Light) {
+        match light {
+            Light::Area { .. } => {}
+            Light::Point {
+                position,
+                intensity,
+                falloff,
+            } => {
+                let context = format!("point light at {}", fmt_vec3(position));
+                self.check_radiance(intensity, &context);
+                self.check_falloff(*falloff, &context);
+            }
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                falloff,
+                inner_angle,
+                outer_angle,
+            } => {
+                let context = format!("spot light at {}", fmt_vec3(position));
+                self.check_radiance(intensity, &context);
+                self.check_falloff(*falloff, &context);
+                if direction.near_zero() {
+                    self.error(format!("{}: direction is zero, the light points nowhere", context));
+                }
+                if !(0.0..=180.0).contains(outer_angle) {
+                    self.error(format!("{}: outer angle {} is outside [0, 180] degrees", context, outer_angle));
+                } else if !(0.0..=*outer_angle).contains(inner_angle) {
+                    self.error(format!(
+                        "{}: inner angle {} is outside [0, {}], the outer angle",
+                        context, inner_angle, outer_angle
+                    ));
+                }
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => {
+                let context = format!("directional light towards {}", fmt_vec3(direction));
+                self.check_radiance(irradiance, &context);
+                if direction.near_zero() {
+                    self.error(format!("{}: direction is zero, the light comes from nowhere", context));
+                }
+                if !(0.0..180.0).contains(angular_diameter) {
+                    self.error(format!(
+                        "{}: angular diameter {} is outside [0, 180) degrees",
+                        context, angular_diameter
+                    ));
+                }
+            }
+        }
+    }
+
+    fn check_falloff(&mut self, falloff: f64, context: &str) {
+        if falloff < 0.0 || !falloff.is_finite() {
+            self.error(format!("{}: falloff {} must not be negative", context, falloff));
+        } else if falloff != 2.0 {
+            self.warn(format!("{}: falloff {} is not the physical inverse square", context, falloff));
+        }
+    }
+
+    pub fn check_camera(&mut self, camera: &Camera) {
+        if camera.image_width <= 0 || camera.aspect_ratio <= 0.0 || !camera.aspect_ratio.is_finite() {
+            self.error(format!(
+                "camera: image width {} with aspect ratio {} gives an empty image",
+                camera.image_width, camera.aspect_ratio
+            ));
+        } else if camera.image_width as f64 / camera.aspect_ratio < 1.0 {
+            self.warn(format!(
+                "camera: aspect ratio {} rounds the image height to zero, it will be clamped to 1",
+                camera.aspect_ratio
+            ));
+        }
+        if camera.samples_per_pixel <= 0 {
+            self.error(format!("camera: samples per pixel is {}, need at least 1", camera.samples_per_pixel));
+        }
+        if camera.max_depth <= 0 {
+            self.warn(format!("camera: max depth {} renders every surface black", camera.max_depth));
+        }
+        if camera.min_bounces < 0 {
+            self.error(format!("camera: min bounces is {}, need at least 0", camera.min_bounces));
+        }
+        if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+            self.error(format!("camera: vertical field of view {} is outside (0, 180) degrees", camera.vfov));
+        }
+
+        if camera.defocus_angle < 0.0 || camera.defocus_angle >= 180.0 {
+            self.error(format!("camera: defocus angle {} is outside [0, 180) degrees", camera.defocus_angle));
+        }
+        if let Some(focus_dist) = camera.focus_dist {
+            if focus_dist <= 0.0 || !focus_dist.is_finite() {
+                self.error(format!("camera: focus distance {} must be positive", focus_dist));
+            }
+        }
+
+        if camera.shutter_close < camera.shutter_open {
+            self.error(format!(
+                "camera: shutter closes at {} before it opens at {}",
+                camera.shutter_close, camera.shutter_open
+            ));
+        }
+
+        match &camera.background {
+            Background::Solid(colour) => self.check_radiance(colour, "background"),
+            Background::Gradient { bottom, top } => {
+                self.check_radiance(bottom, "background");
+                self.check_radiance(top, "background");
+            }
+            Background::Environment(_) => {}
+            Background::EnvironmentLight(light) => {
+                if light.intensity < 0.0 || !light.intensity.is_finite() {
+                    self.error(format!("background: environment intensity {} must not be negative", light.intensity));
+                }
+            }
+            Background::Sky(sky) => {
+                if sky.sun.y.is_nan() || sky.sun.y <= 0.0 {
+                    self.error(format!(
+                        "background: sun direction {} is not above the horizon, the sky model has no night",
+                        fmt_vec3(&sky.sun)
+                    ));
+                }
+                if !(1.7..=10.0).contains(&sky.turbidity) {
+                    self.warn(format!(
+                        "background: turbidity {} is outside [1.7, 10], where the sky model was fitted",
+                        sky.turbidity
+                    ));
+                }
+            }
+        }
+
+        let view = camera.lookfrom - camera.lookat;
+        if view.near_zero() {
+            self.error(format!(
+                "camera: lookfrom and lookat coincide at {}, there is no view direction",
+                fmt_vec3(&camera.lookfrom)
+            ));
+        } else if camera.vup.near_zero() || camera.vup.cross(&view.unit()).near_zero() {
+            self.error(format!(
+                "camera: vup {} is parallel to the view direction, the camera basis is degenerate",
+                fmt_vec3(&camera.vup)
+            ));
+        }
+    }
+}
+
+pub fn validate_scene(world: &dyn Hittable, camera: &Camera) -> Report {
+    let mut report = Report::default();
+    report.check_camera(camera);
+    world.validate(&mut report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HittableList, Sphere};
+    use std::sync::Arc;
+
+    fn sphere(radius: f64, mat_type: Material) -> HittableList {
+        let mut world = HittableList::new();
+        world.push(Arc::new(Sphere {
+            centre: Vec3 { x: 0.0, y: 0.0, z: -1.0 },
+            radius,
+            mat_type,
+        }));
+        world
+    }
+
+    fn solid(x: f64, y: f64, z: f64) -> Texture {
+        Texture::Solid(Vec3 { x, y, z })
+    }
+
+    fn camera() -> Camera {
+        Camera::new(16.0 / 9.0, 400, 10)
+    }
+
+    //Severity and message of every diagnostic, for matching against expectations
+    fn diagnose(world: &dyn Hittable, camera: &Camera) -> Vec<(Severity, String)> {
+        let report = validate_scene(world, camera);
+        report.diagnostics.into_iter().map(|d| (d.severity, d.message)).collect()
+    }
+
+    fn only(diagnostics: &[(Severity, String)], severity: Severity, needle: &str) {
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].0, severity);
+        assert!(diagnostics[0].1.contains(needle), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn plausible_scene_is_clean() {
+        let world = sphere(0.5, Material::Lambertian { albedo: solid(0.5, 0.5, 0.5) });
+        assert!(diagnose(&world, &camera()).is_empty());
+    }
+
+    #[test]
+    fn albedo_outside_unit_range() {
+        let world = sphere(0.5, Material::Lambertian { albedo: solid(1.2, 0.5, 0.5) });
+        only(&diagnose(&world, &camera()), Severity::Warning, "is above 1");
+        let world = sphere(0.5, Material::Lambertian { albedo: solid(-0.1, 0.5, 0.5) });
+        only(&diagnose(&world, &camera()), Severity::Error, "negative or non-finite");
+    }
+
+    #[test]
+    fn non_positive_index_of_refraction() {
+        for idx_refract in [0.0, -1.5, f64::NAN] {
+            let world = sphere(0.5, Material::Dielectric { idx_refract });
+            only(&diagnose(&world, &camera()), Severity::Error, "must be positive");
+        }
+    }
+
+    #[test]
+    fn fuzz_outside_unit_range() {
+        for fuzz in [-0.1, 1.5] {
+            let world = sphere(0.5, Material::Metal { albedo: solid(0.8, 0.8, 0.8), fuzz });
+            only(&diagnose(&world, &camera()), Severity::Warning, "fuzz");
+        }
+    }
+
+    #[test]
+    fn negative_radius_is_fine_for_glass_only() {
+        let world = sphere(-0.4, Material::Dielectric { idx_refract: 1.5 });
+        assert!(diagnose(&world, &camera()).is_empty());
+        let world = sphere(-0.4, Material::Lambertian { albedo: solid(0.5, 0.5, 0.5) });
+        only(&diagnose(&world, &camera()), Severity::Warning, "negative radius");
+    }
+
+    #[test]
+    fn vup_parallel_to_the_view() {
+        let mut camera = camera();
+        camera.vup = camera.lookat - camera.lookfrom;
+        only(&diagnose(&HittableList::new(), &camera), Severity::Error, "parallel to the view direction");
+    }
+
+    #[test]
+    fn coincident_lookfrom_and_lookat() {
+        let mut camera = camera();
+        camera.lookat = camera.lookfrom;
+        only(&diagnose(&HittableList::new(), &camera), Severity::Error, "coincide");
+    }
+
+    #[test]
+    fn zero_size_image() {
+        for (image_width, aspect_ratio) in [(0, 16.0 / 9.0), (400, 0.0)] {
+            let mut camera = camera();
+            camera.image_width = image_width;
+            camera.aspect_ratio = aspect_ratio;
+            only(&diagnose(&HittableList::new(), &camera), Severity::Error, "empty image");
+        }
+    }
+}
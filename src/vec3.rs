@@ -1,6 +1,6 @@
 use crate::rtweekend;
 use rtweekend::{rand0_1, rand_range};
-use std::{f64, iter::repeat_with, ops};
+use std::{f64, iter::repeat_with, ops, str::FromStr};
 
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Vec3 {
@@ -28,10 +28,18 @@ impl Vec3 {
     
     pub fn random_in_unit_sphere() -> Vec3 {
         repeat_with(|| Vec3::random_range(-1.0, 1.0))
-            .filter(|p| p.d_euclidsq() < 1.0)
-            .next()
+            .find(|p| p.d_euclidsq() < 1.0)
             .unwrap()
     }
+    pub fn random_in_unit_disk() -> Vec3 {
+        repeat_with(|| Vec3 {
+            x: rand_range(-1.0, 1.0),
+            y: rand_range(-1.0, 1.0),
+            z: 0.0,
+        })
+        .find(|p| p.d_euclidsq() < 1.0)
+        .unwrap()
+    }
     #[inline]
     pub fn random_unit_vector() -> Vec3 {
         Vec3::random_in_unit_sphere().unit()
@@ -65,6 +73,27 @@ impl Vec3 {
     }
     
 }
+impl FromStr for Vec3 {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        //Parses "x,y,z"
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if parts.len() != 3 {
+            return Err(format!("expected three comma-separated numbers, got '{}'", s));
+        }
+        let mut xyz = [0.0; 3];
+        for (value, part) in xyz.iter_mut().zip(&parts) {
+            *value = part
+                .parse::<f64>()
+                .map_err(|err| format!("invalid component '{}': {}", part, err))?;
+        }
+        Ok(Vec3 {
+            x: xyz[0],
+            y: xyz[1],
+            z: xyz[2],
+        })
+    }
+}
 impl ops::Add<Vec3> for Vec3 {
     type Output = Vec3;
     fn add(self, rhs: Vec3) -> Self::Output {